@@ -41,6 +41,6 @@ fn test_enum_1(passengers: int) {
     let t = Vehicle::Train(true);
     let c1 = Vehicle::Car(Car { passengers, four_doors: true });
     let c2 = Vehicle::Car(Car { passengers, four_doors: false });
-    // assert(t != c1);
-    // assert(c1 != c2);
+    assert(t != c1);
+    assert(c1 != c2);
 }
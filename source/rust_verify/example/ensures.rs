@@ -0,0 +1,24 @@
+extern crate builtin;
+use builtin::*;
+mod pervasive;
+use pervasive::*;
+
+fn add1(x: int) -> int {
+    ensures(result == x + 1);
+    x + 1
+}
+
+fn bad_add1(x: int) -> int {
+    ensures(result == x + 2); // FAILS
+    x + 1
+}
+
+fn main() {}
+
+fn test_ensures_1(x: int) {
+    assert(add1(x) == x + 1);
+}
+
+fn test_ensures_2(x: int) {
+    assert(add1(x) != x + 1); // FAILS
+}
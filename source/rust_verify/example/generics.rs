@@ -0,0 +1,27 @@
+extern crate builtin;
+use builtin::*;
+mod pervasive;
+use pervasive::*;
+
+enum Option<T> {
+    None,
+    Some(T),
+}
+
+spec fn is_some<T>(o: Option<T>) -> bool {
+    match o {
+        Option::None => false,
+        Option::Some(_) => true,
+    }
+}
+
+fn main() {}
+
+fn test_generic_1(x: int) {
+    assert(is_some(Option::Some(x)));
+    assert(!is_some::<int>(Option::None));
+}
+
+fn test_generic_2<T>(t: T) {
+    assert(is_some(Option::Some(t)));
+}
@@ -0,0 +1,47 @@
+extern crate builtin;
+use builtin::*;
+mod pervasive;
+use pervasive::*;
+
+spec fn count_down(n: int) -> int {
+    decreases(n);
+    if n <= 0 {
+        0
+    } else {
+        1 + count_down(n - 1)
+    }
+}
+
+spec fn is_even(n: int) -> bool {
+    decreases(n);
+    if n <= 0 {
+        true
+    } else {
+        is_odd(n - 1)
+    }
+}
+
+spec fn is_odd(n: int) -> bool {
+    decreases(n);
+    if n <= 0 {
+        false
+    } else {
+        is_even(n - 1)
+    }
+}
+
+fn main() {}
+
+fn test_recursion_1() {
+    assert(count_down(0) == 0);
+    assert(count_down(1) == 1);
+    assert(count_down(3) == 3);
+    assert(count_down(3) != 2); // FAILS
+}
+
+fn test_mutual_recursion_1() {
+    assert(is_even(0));
+    assert(is_odd(1));
+    assert(is_even(2));
+    assert(!is_even(1)); // FAILS
+}
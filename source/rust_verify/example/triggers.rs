@@ -0,0 +1,19 @@
+extern crate builtin;
+use builtin::*;
+mod pervasive;
+use pervasive::*;
+
+spec fn f(i: int) -> int {
+    i + 1
+}
+
+spec fn g(i: int) -> int {
+    i + 1
+}
+
+fn main() {}
+
+fn test_trigger_1() {
+    assume(forall(|i: int| with_trigger([[f(i)]], f(i) == g(i))));
+    assert(f(3) == g(3));
+}
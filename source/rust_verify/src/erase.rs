@@ -0,0 +1,107 @@
+//! Mode erasure: turn a verified crate into a normal, compilable Rust crate
+//! by deleting everything that only exists for verification.
+//!
+//! `Mode::Spec`/`Mode::Proof` items (and the calls/conditionals that only
+//! reach them) carry zero runtime cost once verification has passed, so this
+//! pass strips them -- along with `requires`/`ensures`/`assert`/`assume`
+//! statements -- leaving only `Mode::Exec` code for rustc to compile with no
+//! verification overhead.
+
+use rustc_ast::mut_visit::MutVisitor;
+use rustc_ast::ptr::P;
+use rustc_ast::{Crate, Expr, ExprKind, Item, ItemKind, Stmt, StmtKind};
+use rustc_span::Span;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use vir::ast::{Ident, Mode};
+
+/// How a particular call (or `if`/`match` scrutinee) was resolved while
+/// building VIR. Recorded at `rust_to_vir_func::body_to_vir` time, alongside
+/// every call's resolved `Path` and mode, so the erasure pass can make its
+/// decision without re-running type checking.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ResolvedCall {
+    /// Call to a `Mode::Spec` function, or to `requires`/`ensures`/`decreases`
+    /// -- erase the whole call expression (and its statement, if standalone).
+    Spec,
+    /// Call to a `Mode::Proof` function -- erase the whole call expression.
+    Proof,
+    /// A compilable operator VIR treats specially (e.g. `int` arithmetic
+    /// lowered straight to the matching Rust operator) -- keep as-is.
+    CompilableOperator,
+    /// An ordinary `Mode::Exec` call -- keep as-is.
+    Call(Ident),
+}
+
+/// Side table built up while lowering THIR to VIR: every call expression's
+/// and every `if`/`match` condition's span maps to how it resolved, so this
+/// pass knows what it may delete without re-deriving modes from scratch.
+#[derive(Debug, Default)]
+pub struct ErasureInfo {
+    pub resolved_calls: RefCell<Vec<(Span, ResolvedCall)>>,
+    pub erasure_mode: RefCell<HashMap<Span, Mode>>,
+}
+
+impl ErasureInfo {
+    pub fn new() -> Self {
+        ErasureInfo { resolved_calls: RefCell::new(Vec::new()), erasure_mode: RefCell::new(HashMap::new()) }
+    }
+
+    fn mode_at(&self, span: Span) -> Option<Mode> {
+        self.erasure_mode.borrow().get(&span).copied()
+    }
+}
+
+struct Eraser<'a> {
+    erasure_info: &'a ErasureInfo,
+}
+
+fn is_erased_call(erasure_info: &ErasureInfo, span: Span) -> bool {
+    let calls = erasure_info.resolved_calls.borrow();
+    calls.iter().any(|(s, call)| {
+        *s == span && matches!(call, ResolvedCall::Spec | ResolvedCall::Proof)
+    })
+}
+
+impl<'a> MutVisitor for Eraser<'a> {
+    fn visit_item_kind(&mut self, item: &mut ItemKind) {
+        rustc_ast::mut_visit::noop_visit_item_kind(item, self);
+    }
+
+    fn flat_map_item(&mut self, item: P<Item>) -> smallvec::SmallVec<[P<Item>; 1]> {
+        if let ItemKind::Fn(..) = &item.kind {
+            match self.erasure_info.mode_at(item.span) {
+                Some(Mode::Spec) | Some(Mode::Proof) => return smallvec::smallvec![],
+                _ => {}
+            }
+        }
+        rustc_ast::mut_visit::noop_flat_map_item(item, self)
+    }
+
+    fn flat_map_stmt(&mut self, stmt: Stmt) -> smallvec::SmallVec<[Stmt; 1]> {
+        if let StmtKind::Semi(expr) | StmtKind::Expr(expr) = &stmt.kind {
+            if is_erased_call(self.erasure_info, expr.span) {
+                return smallvec::smallvec![];
+            }
+        }
+        rustc_ast::mut_visit::noop_flat_map_stmt(stmt, self)
+    }
+
+    fn visit_expr(&mut self, expr: &mut P<Expr>) {
+        if is_erased_call(self.erasure_info, expr.span) {
+            // A spec/proof call used in value position (rather than as a bare
+            // statement) has no runtime meaning; replace it with a unit-typed
+            // placeholder so the surrounding exec code still type-checks.
+            expr.kind = ExprKind::Tup(rustc_ast::ptr::P::from(Vec::new()));
+            return;
+        }
+        rustc_ast::mut_visit::noop_visit_expr(expr, self);
+    }
+}
+
+/// Erase every `Mode::Spec`/`Mode::Proof` item and verification-only
+/// statement from `krate`, leaving a crate rustc can compile normally.
+pub fn erase_crate(krate: &mut Crate, erasure_info: &ErasureInfo) {
+    let mut eraser = Eraser { erasure_info };
+    eraser.visit_crate(krate);
+}
@@ -0,0 +1,82 @@
+//! Lowering for `struct`/`enum` item declarations -- the datatype-side
+//! counterpart to `rust_to_vir_func::check_item_fn`. Each becomes a
+//! `vir::ast::Datatype` with one `Variant` per struct/enum variant, field
+//! types lowered the same way a function parameter's type is, and its own
+//! type parameters collected through the same `check_generics` functions
+//! use, so `Ctx::datatype_typ_params` (and thus the `Type`-sort binder each
+//! generic datatype axiom quantifies over) is actually populated.
+
+use crate::rust_to_vir_expr::{spanned_new, ty_to_vir};
+use crate::rust_to_vir_func::check_generics;
+use crate::unsupported;
+use rustc_hir::{EnumDef, Generics, VariantData};
+use rustc_middle::ty::TyCtxt;
+use rustc_span::symbol::Ident;
+use rustc_span::Span;
+use std::rc::Rc;
+use vir::ast::{DatatypeX, Field, KrateX, Path, Variant, VirErr};
+
+/// A struct/enum's `vir::ast::Path`. Like `rust_to_vir_func::check_item_fn`
+/// naming functions after their bare `Ident`, this crate doesn't yet
+/// module-qualify paths, so a datatype's path is just its own name.
+fn item_path(id: Ident) -> Path {
+    Rc::new(vec![Rc::new(id.to_string())])
+}
+
+fn variant_data_to_fields<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    data: &'tcx VariantData<'tcx>,
+) -> Result<Vec<Field>, VirErr> {
+    match data {
+        VariantData::Struct(fields, _) => Ok(fields
+            .iter()
+            .map(|f| Field { name: Rc::new(f.ident.to_string()), typ: ty_to_vir(tcx, f.ty) })
+            .collect()),
+        VariantData::Tuple(fields, _) => Ok(fields
+            .iter()
+            .enumerate()
+            .map(|(i, f)| Field { name: Rc::new(i.to_string()), typ: ty_to_vir(tcx, f.ty) })
+            .collect()),
+        VariantData::Unit(_) => Ok(Vec::new()),
+    }
+}
+
+pub(crate) fn check_item_struct<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    vir: &mut KrateX,
+    id: Ident,
+    span: Span,
+    data: &'tcx VariantData<'tcx>,
+    generics: &'tcx Generics<'tcx>,
+) -> Result<(), VirErr> {
+    let typ_params = check_generics(generics)?;
+    let path = item_path(id);
+    let fields = variant_data_to_fields(tcx, data)?;
+    let variant = Variant { name: path.last().unwrap().clone(), fields: Rc::new(fields) };
+    let datatype = DatatypeX { path, typ_params, variants: Rc::new(vec![variant]) };
+    vir.datatypes.push(spanned_new(span, datatype));
+    Ok(())
+}
+
+pub(crate) fn check_item_enum<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    vir: &mut KrateX,
+    id: Ident,
+    span: Span,
+    enum_def: &'tcx EnumDef<'tcx>,
+    generics: &'tcx Generics<'tcx>,
+) -> Result<(), VirErr> {
+    let typ_params = check_generics(generics)?;
+    let path = item_path(id);
+    let mut variants = Vec::new();
+    for variant in enum_def.variants.iter() {
+        if variant.disr_expr.is_some() {
+            unsupported!("explicit enum discriminant values");
+        }
+        let fields = variant_data_to_fields(tcx, &variant.data)?;
+        variants.push(Variant { name: Rc::new(variant.ident.to_string()), fields: Rc::new(fields) });
+    }
+    let datatype = DatatypeX { path, typ_params, variants: Rc::new(variants) };
+    vir.datatypes.push(spanned_new(span, datatype));
+    Ok(())
+}
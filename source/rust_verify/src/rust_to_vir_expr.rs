@@ -0,0 +1,467 @@
+//! THIR -> VIR lowering for function bodies.
+//!
+//! `rustc_mir_build::thir` gives each function body as a single
+//! self-contained expression tree (this snapshot has no separate
+//! arena-indexed `Thir` context to thread through, so, like
+//! `rust_to_vir_func::body_to_vir` already assumes, a `thir::Expr` owns its
+//! subexpressions directly). Lowering is mostly structural: THIR's
+//! `ExprKind` becomes the matching `vir::ast::ExprX`, and a handful of plain
+//! function calls (`requires`/`ensures`/`decreases`) are left as ordinary
+//! `ExprX::Call` nodes -- `rust_to_vir_func::read_header_block` recognizes
+//! them by name afterward, rather than this module giving them their own
+//! VIR node.
+
+use rustc_middle::ty::TyCtxt;
+use rustc_mir_build::thir;
+use rustc_span::symbol::Ident;
+use rustc_span::Span;
+use std::rc::Rc;
+use vir::ast::{BinaryOp, Constant, Expr, ExprX, Mode, Quant, StmtX, Typ, TypX, UnaryOp, VirErr};
+use vir::def::{Spanned, RESULT_IDENT};
+
+use crate::erase::{ErasureInfo, ResolvedCall};
+
+/// The single point where a rustc `Span` becomes the opaque `air::ast::Span`
+/// every VIR node carries. `air::ast::Span` is a plain `Rc<String>`-shaped
+/// handle (see the way `vir::def::Spanned::span` is only ever `.clone()`d,
+/// never inspected), so a span's debug text is enough to identify it later.
+pub(crate) fn spanned_new<X>(span: Span, x: X) -> Rc<Spanned<X>> {
+    Spanned::new(Rc::new(format!("{:?}", span)), x)
+}
+
+pub(crate) fn ident_to_var(id: &Ident) -> String {
+    id.to_string()
+}
+
+pub(crate) fn pat_to_var(pat: &rustc_hir::Pat) -> String {
+    match pat.kind {
+        rustc_hir::PatKind::Binding(_, _, ident, _) => ident.to_string(),
+        _ => panic!("unsupported pattern in parameter position"),
+    }
+}
+
+/// `spec`/`proof`/`exec` are attached to an item's attribute list by the
+/// front end that parses those keywords ahead of an ordinary `fn`; default
+/// to `Exec` for a plain `fn` with none of the three.
+pub(crate) fn get_mode(attrs: &[rustc_ast::Attribute]) -> Mode {
+    for attr in attrs {
+        if let Some(id) = attr.ident() {
+            match id.as_str() {
+                "spec" => return Mode::Spec,
+                "proof" => return Mode::Proof,
+                "exec" => return Mode::Exec,
+                _ => {}
+            }
+        }
+    }
+    Mode::Exec
+}
+
+/// `#[fuel(n)]` overrides how many times a recursive spec function's
+/// definition axiom may unfold before a caller needs more fuel (see
+/// `context::definition_axioms`); default to 1.
+pub(crate) fn get_fuel(attrs: &[rustc_ast::Attribute]) -> u32 {
+    for attr in attrs {
+        if attr.has_name(rustc_span::symbol::Symbol::intern("fuel")) {
+            if let Some(lit) = attr.value_str() {
+                if let Ok(n) = lit.as_str().parse::<u32>() {
+                    return n;
+                }
+            }
+        }
+    }
+    1
+}
+
+pub(crate) fn ty_to_vir<'tcx>(tcx: TyCtxt<'tcx>, ty: &'tcx rustc_hir::Ty<'tcx>) -> Typ {
+    match &ty.kind {
+        rustc_hir::TyKind::Path(rustc_hir::QPath::Resolved(_, path)) => {
+            let seg = path.segments.last().expect("non-empty type path");
+            match seg.ident.as_str() {
+                "bool" => Rc::new(TypX::Bool),
+                "int" | "nat" | "u64" | "usize" | "i64" | "u32" | "i32" => Rc::new(TypX::Int),
+                name => match path.res {
+                    rustc_hir::def::Res::Def(rustc_hir::def::DefKind::TyParam, _) => {
+                        Rc::new(TypX::TypParam(Rc::new(name.to_string())))
+                    }
+                    _ => {
+                        let typs: Vec<Typ> = seg
+                            .args()
+                            .args
+                            .iter()
+                            .filter_map(|a| match a {
+                                rustc_hir::GenericArg::Type(t) => Some(ty_to_vir(tcx, t)),
+                                _ => None,
+                            })
+                            .collect();
+                        Rc::new(TypX::Datatype(Rc::new(vec![Rc::new(name.to_string())]), Rc::new(typs)))
+                    }
+                },
+            }
+        }
+        _ => panic!("unsupported type"),
+    }
+}
+
+fn stmt_block(stmts: Vec<vir::ast::Stmt>, last: Option<Expr>, span: Span) -> Expr {
+    spanned_new(span, ExprX::Block(Rc::new(stmts), last))
+}
+
+/// A struct/enum's `vir::ast::Path` from its `rustc_middle` `DefId` --
+/// matches `rust_to_vir_adt::item_path`'s "bare name, no module
+/// qualification" convention, just resolved from the other direction (a
+/// THIR type's `AdtDef` instead of the `hir::Item` itself).
+fn adt_path<'tcx>(tcx: TyCtxt<'tcx>, did: rustc_hir::def_id::DefId) -> vir::ast::Path {
+    Rc::new(vec![Rc::new(tcx.item_name(did).to_string())])
+}
+
+/// Same job as `ty_to_vir`, but starting from a `rustc_middle::ty::Ty`
+/// (a THIR expression's resolved, fully-substituted type) instead of the
+/// `rustc_hir::Ty` syntax a parameter/field declaration is written with --
+/// needed wherever a box/unbox coercion has to name the *actual* type an
+/// expression produces, which isn't written down anywhere in the source.
+fn mir_ty_to_vir<'tcx>(tcx: TyCtxt<'tcx>, ty: rustc_middle::ty::Ty<'tcx>) -> Typ {
+    match ty.kind() {
+        rustc_middle::ty::TyKind::Bool => Rc::new(TypX::Bool),
+        rustc_middle::ty::TyKind::Int(_) | rustc_middle::ty::TyKind::Uint(_) => Rc::new(TypX::Int),
+        rustc_middle::ty::TyKind::Param(p) => Rc::new(TypX::TypParam(Rc::new(p.name.to_string()))),
+        rustc_middle::ty::TyKind::Adt(adt_def, substs) => {
+            let typs: Vec<Typ> =
+                substs.types().map(|t| mir_ty_to_vir(tcx, t)).collect();
+            Rc::new(TypX::Datatype(adt_path(tcx, adt_def.did()), Rc::new(typs)))
+        }
+        _ => panic!("unsupported type"),
+    }
+}
+
+/// Is `field`'s declaration itself generic (i.e. its type, as written on
+/// the struct/enum item, is a bare type parameter) rather than a concrete
+/// type? Answered from the *unsubstituted* field type (`tcx.type_of`,
+/// before any of the datatype's own type parameters are instantiated),
+/// since that's what decided the `Poly` sort `context::datatype_commands`
+/// declared the field's accessor at -- a concretely-typed field never
+/// needs boxing even if the datatype itself is generic.
+fn field_is_generic(tcx: TyCtxt<'_>, field_def_id: rustc_hir::def_id::DefId) -> bool {
+    matches!(tcx.type_of(field_def_id).kind(), rustc_middle::ty::TyKind::Param(_))
+}
+
+fn box_expr(span: Span, concrete: Typ, v: Expr) -> Expr {
+    let name = vir::def::box_fn_name(&concrete);
+    spanned_new(span, ExprX::Call(Rc::new(name.to_string()), Rc::new(vec![concrete]), Rc::new(vec![v])))
+}
+
+fn unbox_expr(span: Span, concrete: Typ, v: Expr) -> Expr {
+    let name = vir::def::unbox_fn_name(&concrete);
+    spanned_new(span, ExprX::Call(Rc::new(name.to_string()), Rc::new(vec![concrete]), Rc::new(vec![v])))
+}
+
+/// One `match` arm: which variant it matches, names bound to each of that
+/// variant's fields (in field order -- a `_`/wildcard subpattern still
+/// needs a placeholder name so the binder list's arity matches the
+/// variant's field count, since `sst_to_air`'s arm-to-accessor desugaring
+/// indexes into it positionally).
+fn pat_to_arm<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    pat: &'tcx thir::Pat<'tcx>,
+    body: &'tcx thir::Expr<'tcx>,
+    erasure_info: &ErasureInfo,
+) -> Result<vir::ast::Arm, VirErr> {
+    match &pat.kind {
+        thir::PatKind::Variant { adt_def, variant_index, subpatterns } => {
+            let variant = adt_def.variant(*variant_index);
+            // One binder per declared field, keyed by the field's own
+            // name (so `sst_to_air::match_to_air` looks up the same
+            // accessor `rust_to_vir_adt` declared it under) and defaulted
+            // to a fresh, unused local name until a subpattern overrides it.
+            let mut binders: Vec<vir::ast::Binder<vir::ast::Ident>> = variant
+                .fields
+                .iter()
+                .enumerate()
+                .map(|(i, f)| vir::ast::Binder {
+                    name: Rc::new(f.name.to_string()),
+                    a: Rc::new(format!("_{}", i)),
+                })
+                .collect();
+            for fp in subpatterns.iter() {
+                if let thir::PatKind::Binding { name, .. } = &fp.pattern.kind {
+                    binders[fp.field.index()].a = Rc::new(name.to_string());
+                }
+            }
+            let vbody = expr_to_vir(tcx, body, erasure_info)?;
+            Ok(vir::ast::Arm {
+                path: adt_path(tcx, adt_def.did()),
+                variant: Rc::new(variant.name.to_string()),
+                binders: Rc::new(binders),
+                body: vbody,
+            })
+        }
+        _ => panic!("unsupported match pattern"),
+    }
+}
+
+pub(crate) fn expr_to_vir<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    expr: &'tcx thir::Expr<'tcx>,
+    erasure_info: &ErasureInfo,
+) -> Result<Expr, VirErr> {
+    let span = expr.span;
+    match &expr.kind {
+        thir::ExprKind::Scope { value } => expr_to_vir(tcx, value, erasure_info),
+        // Only ever appears, after `peel_scope`, as the closure literal
+        // `forall`/`exists` is called with -- that's pulled apart directly
+        // in the `Call` arm below, never reached generically from here.
+        thir::ExprKind::Closure { .. } => {
+            panic!("internal error: closure used outside a forall/exists argument")
+        }
+        thir::ExprKind::LiteralBool(b) => Ok(spanned_new(span, ExprX::Const(Constant::Bool(*b)))),
+        thir::ExprKind::LiteralInt(s) => {
+            Ok(spanned_new(span, ExprX::Const(Constant::Int(Rc::new(s.clone())))))
+        }
+        // `result`, inside an `ensures` clause, isn't bound by any real
+        // local -- it names the function's own return value -- so it's
+        // recognized here by its literal source spelling and lowered
+        // straight to the reserved `RESULT_IDENT`, the same name
+        // `rust_to_vir_func`/`Ctx::postcondition_commands` substitute for
+        // it at VC- and axiom-emission time.
+        thir::ExprKind::VarRef(id) if id.as_str() == "result" => {
+            Ok(spanned_new(span, ExprX::Var(Rc::new(RESULT_IDENT.to_string()))))
+        }
+        thir::ExprKind::VarRef(id) => Ok(spanned_new(span, ExprX::Var(Rc::new(ident_to_var(id))))),
+        thir::ExprKind::Unary { op, arg } => {
+            let vop = match op {
+                thir::UnOp::Not => UnaryOp::Not,
+                thir::UnOp::Neg => UnaryOp::Neg,
+            };
+            let varg = expr_to_vir(tcx, arg, erasure_info)?;
+            Ok(spanned_new(span, ExprX::Unary(vop, varg)))
+        }
+        thir::ExprKind::Binary { op, lhs, rhs } => {
+            let vop = match op {
+                thir::BinOp::And => BinaryOp::And,
+                thir::BinOp::Or => BinaryOp::Or,
+                thir::BinOp::Eq => BinaryOp::Eq,
+                thir::BinOp::Ne => BinaryOp::Ne,
+                thir::BinOp::Le => BinaryOp::Le,
+                thir::BinOp::Ge => BinaryOp::Ge,
+                thir::BinOp::Lt => BinaryOp::Lt,
+                thir::BinOp::Gt => BinaryOp::Gt,
+                thir::BinOp::Add => BinaryOp::Add,
+                thir::BinOp::Sub => BinaryOp::Sub,
+                thir::BinOp::Mul => BinaryOp::Mul,
+            };
+            let vlhs = expr_to_vir(tcx, lhs, erasure_info)?;
+            let vrhs = expr_to_vir(tcx, rhs, erasure_info)?;
+            Ok(spanned_new(span, ExprX::Binary(vop, vlhs, vrhs)))
+        }
+        thir::ExprKind::If { cond, then, els } => {
+            let vcond = expr_to_vir(tcx, cond, erasure_info)?;
+            let vthen = expr_to_vir(tcx, then, erasure_info)?;
+            let vels = els.map(|e| expr_to_vir(tcx, e, erasure_info)).transpose()?;
+            Ok(spanned_new(span, ExprX::If(vcond, vthen, vels)))
+        }
+        thir::ExprKind::Block { stmts, expr: last } => {
+            let mut vstmts = Vec::new();
+            for stmt in stmts.iter() {
+                let ve = expr_to_vir(tcx, stmt, erasure_info)?;
+                vstmts.push(Spanned::new(ve.span.clone(), StmtX::Expr(ve)));
+            }
+            let vlast = last.map(|e| expr_to_vir(tcx, e, erasure_info)).transpose()?;
+            Ok(stmt_block(vstmts, vlast, span))
+        }
+        thir::ExprKind::Match { scrutinee, arms } => {
+            let vscrutinee = expr_to_vir(tcx, scrutinee, erasure_info)?;
+            let mut varms = Vec::new();
+            for arm in arms.iter() {
+                varms.push(pat_to_arm(tcx, arm.pattern, arm.body, erasure_info)?);
+            }
+            Ok(spanned_new(span, ExprX::Match(vscrutinee, Rc::new(varms))))
+        }
+        thir::ExprKind::Adt { adt_def, variant_index, fields, .. } => {
+            let variant = adt_def.variant(*variant_index);
+            let mut binders = Vec::new();
+            for fe in fields.iter() {
+                let field_def = &variant.fields[fe.name.index()];
+                let mut v = expr_to_vir(tcx, fe.expr, erasure_info)?;
+                if field_is_generic(tcx, field_def.did) {
+                    v = box_expr(span, mir_ty_to_vir(tcx, fe.expr.ty), v);
+                }
+                binders.push(vir::ast::Binder { name: Rc::new(field_def.name.to_string()), a: v });
+            }
+            Ok(spanned_new(
+                span,
+                ExprX::Ctor(adt_path(tcx, adt_def.did()), Rc::new(variant.name.to_string()), Rc::new(binders)),
+            ))
+        }
+        thir::ExprKind::Field { lhs, variant_index, name } => {
+            let adt_def = match lhs.ty.kind() {
+                rustc_middle::ty::TyKind::Adt(adt_def, _) => adt_def,
+                _ => panic!("field projection on a non-struct/enum expression"),
+            };
+            let variant = adt_def.variant(*variant_index);
+            let field_def = &variant.fields[name.index()];
+            let vlhs = expr_to_vir(tcx, lhs, erasure_info)?;
+            let projected = spanned_new(
+                span,
+                ExprX::Field(
+                    adt_path(tcx, adt_def.did()),
+                    Rc::new(variant.name.to_string()),
+                    Rc::new(field_def.name.to_string()),
+                    vlhs,
+                ),
+            );
+            Ok(if field_is_generic(tcx, field_def.did) {
+                unbox_expr(span, mir_ty_to_vir(tcx, expr.ty), projected)
+            } else {
+                projected
+            })
+        }
+        // Only ever appears as `with_trigger`'s trigger-group argument
+        // (`[[f(i)], [g(i)]]`) -- an ordinary Rust array literal has no
+        // other use in this spec-only sublanguage.
+        thir::ExprKind::Array { fields } => {
+            let mut vfields = Vec::new();
+            for f in fields.iter() {
+                vfields.push(expr_to_vir(tcx, f, erasure_info)?);
+            }
+            Ok(spanned_new(span, ExprX::Array(Rc::new(vfields))))
+        }
+        thir::ExprKind::Call { fun, args } => {
+            let def_id = call_def_id(fun);
+            let name = tcx.item_name(def_id).to_string();
+            // `forall`/`exists` take a closure, not an ordinary argument
+            // list -- recognized here, before the closure's single
+            // parameter and body are lowered generically, rather than
+            // giving `thir::ExprKind::Closure` a standalone VIR form (a
+            // closure has no meaning outside this one position). Gated on
+            // `is_spec_builtin` (not just the bare name) so a user's own
+            // function that happens to be named `forall`/`exists` is
+            // still lowered as an ordinary call.
+            let is_builtin = is_spec_builtin(tcx, def_id, &name);
+            if is_builtin {
+                if let Some(quant) = quant_for_name(&name) {
+                    if args.len() == 1 {
+                        if let thir::ExprKind::Closure { params, body } = &peel_scope(&args[0]).kind {
+                            let binders: Vec<vir::ast::Binder<Typ>> = params
+                                .iter()
+                                .map(|(id, ty)| vir::ast::Binder {
+                                    name: Rc::new(id.to_string()),
+                                    a: ty_to_vir(tcx, *ty),
+                                })
+                                .collect();
+                            let vbody = expr_to_vir(tcx, body, erasure_info)?;
+                            erasure_info.resolved_calls.borrow_mut().push((span, ResolvedCall::Spec));
+                            return Ok(spanned_new(span, ExprX::Quant(quant, Rc::new(binders), vbody)));
+                        }
+                    }
+                    // Builtin `forall`/`exists`, but not a single closure
+                    // literal argument -- report it rather than falling
+                    // through to a generic call (which would reach
+                    // `sst_to_air` as an unresolvable call to "forall"/
+                    // "exists").
+                    return Err(spanned_new(
+                        span,
+                        "forall/exists require a single closure literal argument".to_string(),
+                    ));
+                }
+            }
+            erasure_info
+                .resolved_calls
+                .borrow_mut()
+                .push((span, resolved_call(tcx, def_id, &name, is_builtin)));
+            // The genuine `with_trigger` builtin is lowered under a
+            // reserved name (never producible by a real Rust identifier,
+            // same trick as `RESULT_IDENT`/`FUEL_PARAM`) so
+            // `sst_to_air::manual_triggers`'s downstream name match can't
+            // be fooled by a user's own same-named function the way a
+            // bare string compare could.
+            let lowered_name = if is_builtin && name == "with_trigger" {
+                vir::def::WITH_TRIGGER_IDENT.to_string()
+            } else {
+                name
+            };
+            let mut vargs = Vec::new();
+            for a in args.iter() {
+                vargs.push(expr_to_vir(tcx, a, erasure_info)?);
+            }
+            Ok(spanned_new(
+                span,
+                ExprX::Call(Rc::new(lowered_name), Rc::new(vec![]), Rc::new(vargs)),
+            ))
+        }
+    }
+}
+
+/// The callee's `DefId`, resolved from the THIR `FnDef` type of the
+/// expression in call position -- this snapshot has no module-qualified
+/// paths (see `rust_to_vir_adt::item_path`), so a function's VIR name
+/// (used by `resolved_call` and the `Call` arm above) is just its own
+/// `Ident`, taken straight from this `DefId`.
+fn call_def_id<'tcx>(fun: &'tcx thir::Expr<'tcx>) -> rustc_hir::def_id::DefId {
+    match fun.ty.kind() {
+        rustc_middle::ty::TyKind::FnDef(def_id, _) => *def_id,
+        _ => panic!("call to a non-function-item expression"),
+    }
+}
+
+/// Strip any `Scope` wrapper(s) around `e` -- the same no-op-at-the-VIR-level
+/// wrapper `expr_to_vir`'s own first match arm strips while lowering
+/// normally, but needed again here since `forall`/`exists`'s closure
+/// argument is inspected directly (by `thir::ExprKind`) rather than
+/// recursed into via `expr_to_vir`.
+fn peel_scope<'tcx>(mut e: &'tcx thir::Expr<'tcx>) -> &'tcx thir::Expr<'tcx> {
+    while let thir::ExprKind::Scope { value } = &e.kind {
+        e = value;
+    }
+    e
+}
+
+/// Which quantifier (if any) the builtin named `name` introduces.
+fn quant_for_name(name: &str) -> Option<Quant> {
+    match name {
+        "forall" => Some(Quant::Forall),
+        "exists" => Some(Quant::Exists),
+        _ => None,
+    }
+}
+
+/// The handful of functions `rust_to_vir_func::read_header_block` (and
+/// `quant`/trigger handling) recognize by name instead of resolving to a
+/// VIR function -- none of them exist at runtime, so every call to one is
+/// erased exactly like a call to a real `Mode::Spec` function. Every
+/// example's `extern crate builtin; use builtin::*;` is this snapshot's
+/// only source of them, so they're recognized by *both* name and home
+/// crate -- by name alone, an ordinary `Mode::Exec` function a user
+/// happened to name e.g. `assert` would be misclassified as
+/// verification-only and erased out of the compiled program.
+fn is_spec_builtin(tcx: TyCtxt<'_>, def_id: rustc_hir::def_id::DefId, name: &str) -> bool {
+    let from_builtin_crate = tcx.crate_name(def_id.krate).as_str() == "builtin";
+    from_builtin_crate
+        && matches!(
+            name,
+            "requires" | "ensures" | "decreases" | "assert" | "assume" | "forall" | "exists" | "with_trigger"
+        )
+}
+
+/// How `erase::Eraser` should treat a call to `def_id` (named `name`):
+/// erase it outright if it's one of the verification-only builtins or a
+/// `Mode::Spec`/`Mode::Proof` function, otherwise keep it as an ordinary
+/// exec call. Resolved from `def_id`'s own attributes rather than this
+/// crate's own function list, since this snapshot keeps no such registry
+/// (see the module doc on `get_mode`). `is_builtin` is passed in rather than
+/// recomputed, since every call site has already run `is_spec_builtin` to
+/// check for `forall`/`exists`.
+fn resolved_call<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    def_id: rustc_hir::def_id::DefId,
+    name: &str,
+    is_builtin: bool,
+) -> ResolvedCall {
+    if is_builtin {
+        return ResolvedCall::Spec;
+    }
+    match get_mode(tcx.get_attrs_unchecked(def_id)) {
+        Mode::Spec => ResolvedCall::Spec,
+        Mode::Proof => ResolvedCall::Proof,
+        Mode::Exec => ResolvedCall::Call(Rc::new(name.to_string())),
+    }
+}
@@ -1,3 +1,4 @@
+use crate::erase::ErasureInfo;
 use crate::rust_to_vir_expr::{
     expr_to_vir, get_fuel, get_mode, ident_to_var, pat_to_var, spanned_new, ty_to_vir,
 };
@@ -9,29 +10,47 @@ use rustc_mir_build::thir;
 use rustc_span::symbol::Ident;
 use rustc_span::Span;
 use std::rc::Rc;
-use vir::ast::{ExprX, Exprs, FunctionX, KrateX, Mode, ParamX, StmtX, VirErr};
-use vir::def::Spanned;
+use vir::ast::{ExprX, Exprs, FunctionX, Idents, KrateX, Mode, ParamX, StmtX, VirErr};
+use vir::def::{Spanned, RESULT_IDENT};
 
 #[derive(Clone, Debug)]
 struct Header {
     hidden: Vec<vir::ast::Ident>,
     require: Exprs,
+    ensure: Exprs,
+    decreases: Exprs,
 }
 
 fn read_header_block(block: &mut Vec<vir::ast::Stmt>) -> Result<Header, VirErr> {
     let mut hidden: Vec<vir::ast::Ident> = Vec::new();
     let mut require: Option<Exprs> = None;
+    let mut ensure: Option<Exprs> = None;
+    let mut decreases: Option<Exprs> = None;
     let mut n = 0;
     for stmt in block.iter() {
         match &stmt.x {
             StmtX::Expr(expr) => match &expr.x {
-                ExprX::Call(x, es) if x.as_str() == "requires" => {
+                ExprX::Call(x, _, es) if x.as_str() == "requires" => {
                     if require.is_some() {
                         return Err(Spanned::new(stmt.span.clone(),
                             "only one call to requires allowed (use requires([e1, ..., en]) for multiple expressions".to_string()));
                     }
                     require = Some(es.clone());
                 }
+                ExprX::Call(x, _, es) if x.as_str() == "ensures" => {
+                    if ensure.is_some() {
+                        return Err(Spanned::new(stmt.span.clone(),
+                            "only one call to ensures allowed (use ensures([e1, ..., en]) for multiple expressions".to_string()));
+                    }
+                    ensure = Some(es.clone());
+                }
+                ExprX::Call(x, _, es) if x.as_str() == "decreases" => {
+                    if decreases.is_some() {
+                        return Err(Spanned::new(stmt.span.clone(),
+                            "only one call to decreases allowed (use decreases([e1, ..., en]) for multiple measures".to_string()));
+                    }
+                    decreases = Some(es.clone());
+                }
                 ExprX::Fuel(x, 0) => {
                     hidden.push(x.clone());
                 }
@@ -42,7 +61,12 @@ fn read_header_block(block: &mut Vec<vir::ast::Stmt>) -> Result<Header, VirErr>
         n += 1;
     }
     *block = block[n..].to_vec();
-    Ok(Header { hidden, require: require.unwrap_or(Rc::new(vec![])) })
+    Ok(Header {
+        hidden,
+        require: require.unwrap_or(Rc::new(vec![])),
+        ensure: ensure.unwrap_or(Rc::new(vec![])),
+        decreases: decreases.unwrap_or(Rc::new(vec![])),
+    })
 }
 
 fn read_header(body: &mut vir::ast::Expr) -> Result<Header, VirErr> {
@@ -61,6 +85,7 @@ fn body_to_vir<'tcx>(
     tcx: TyCtxt<'tcx>,
     id: &BodyId,
     body: &'tcx Body<'tcx>,
+    erasure_info: &ErasureInfo,
 ) -> Result<vir::ast::Expr, VirErr> {
     let did = id.hir_id.owner;
     let arena = thir::Arena::default();
@@ -70,7 +95,10 @@ fn body_to_vir<'tcx>(
         &arena,
         &body.value,
     );
-    expr_to_vir(tcx, expr)
+    // `expr_to_vir` records, for every call and `if`/`match` it lowers, the
+    // resolved callee `Path` and its `Mode` into `erasure_info`, so `erase`
+    // can later delete spec/proof calls without re-deriving modes.
+    expr_to_vir(tcx, expr, erasure_info)
 }
 
 fn check_fn_decl<'tcx>(
@@ -89,14 +117,28 @@ fn check_fn_decl<'tcx>(
     }
 }
 
-pub(crate) fn check_generics<'tcx>(generics: &'tcx Generics<'tcx>) -> Result<(), VirErr> {
+// Collect the function or datatype's type parameters. Each one becomes an
+// opaque SMT sort argument; values of that type are represented boxed
+// through the universal `Poly` type (`box`/`unbox` coercions are inserted by
+// `rust_to_vir_expr::expr_to_vir` at instantiation and projection sites), so
+// no trait bounds are needed here -- a where clause would only make sense
+// once bounded polymorphism is supported, so it stays unsupported for now.
+pub(crate) fn check_generics<'tcx>(generics: &'tcx Generics<'tcx>) -> Result<Idents, VirErr> {
     match generics {
         Generics { params, where_clause, span: _ } => {
-            unsupported_unless!(params.len() == 0, "generics");
             unsupported_unless!(where_clause.predicates.len() == 0, "where clause");
+            let mut typ_params: Vec<vir::ast::Ident> = Vec::new();
+            for param in params.iter() {
+                match param.kind {
+                    rustc_hir::GenericParamKind::Type { .. } => {
+                        typ_params.push(Rc::new(param.name.ident().to_string()));
+                    }
+                    _ => unsupported!("lifetime/const generic parameters"),
+                }
+            }
+            Ok(Rc::new(typ_params))
         }
     }
-    Ok(())
 }
 
 pub(crate) fn check_item_fn<'tcx>(
@@ -104,10 +146,17 @@ pub(crate) fn check_item_fn<'tcx>(
     krate: &'tcx Crate<'tcx>,
     vir: &mut KrateX,
     id: Ident,
+    // The enclosing AST `Item`'s span (attrs + signature + body), as opposed
+    // to `sig.span` which covers only the declarator. `erase::Eraser` looks
+    // up `erasure_mode` by `Item::span` (it deletes whole items), so that is
+    // the span this function must record the mode against -- recording under
+    // `sig.span` would never be found and no item would ever be erased.
+    item_span: Span,
     attrs: &[Attribute],
     sig: &'tcx FnSig<'tcx>,
     generics: &Generics,
     body_id: &BodyId,
+    erasure_info: &ErasureInfo,
 ) -> Result<(), VirErr> {
     let ret = match sig {
         FnSig {
@@ -119,16 +168,9 @@ pub(crate) fn check_item_fn<'tcx>(
             check_fn_decl(tcx, decl)?
         }
     };
-    check_generics(generics)?;
+    let typ_params = check_generics(generics)?;
     let mode = get_mode(attrs);
     let fuel = get_fuel(attrs);
-    match (mode, &ret) {
-        (Mode::Exec, None) | (Mode::Proof, None) => {}
-        (Mode::Exec, Some(_)) | (Mode::Proof, Some(_)) => {
-            unsupported!("non-spec function return values");
-        }
-        (Mode::Spec, _) => {}
-    }
     let body = &krate.bodies[body_id];
     let Body { params, value: _, generator_kind } = body;
     let mut vir_params: Vec<vir::ast::Param> = Vec::new();
@@ -145,21 +187,39 @@ pub(crate) fn check_item_fn<'tcx>(
             unsupported!("generator_kind", generator_kind);
         }
     }
-    let mut vir_body = body_to_vir(tcx, body_id, body)?;
+    let mut vir_body = body_to_vir(tcx, body_id, body, erasure_info)?;
     let header = read_header(&mut vir_body)?;
-    if mode == Mode::Spec && header.require.len() > 0 {
+    if mode == Mode::Spec && (header.require.len() > 0 || header.ensure.len() > 0) {
         let s = "spec functions cannot have requires/ensures";
         return Err(spanned_new(sig.span, s.to_string()));
     }
+    if header.ensure.len() > 0 && ret.is_none() {
+        let s = format!("ensures clauses bind `{}`, which requires a return type", RESULT_IDENT);
+        return Err(spanned_new(sig.span, s));
+    }
+    // Record this function's own mode against the *item's* span (not
+    // `sig.span`, which covers only the declarator) so `erase::Eraser`'s
+    // `flat_map_item`, which looks up `item.span`, can find it and drop the
+    // whole item when it is `Mode::Spec`/`Mode::Proof`.
+    erasure_info.erasure_mode.borrow_mut().insert(item_span, mode);
+    // Exec/proof functions may now return a value: the returned expression is
+    // bound to `result` (a reserved identifier, resolved by
+    // `rust_to_vir_expr::expr_to_vir`) which is visible inside `ensures`.
+    // `Ctx::postcondition_commands` emits a VC at each of the function's
+    // return points and assumes the same ensures clauses, with `result`
+    // substituted for the call expression, at every call site.
     let name = Rc::new(ident_to_var(&id));
     let params = Rc::new(vir_params);
     let func = FunctionX {
         name,
+        typ_params,
         mode,
         fuel,
         params,
         ret,
         require: header.require,
+        ensure: header.ensure,
+        decreases: header.decreases,
         hidden: Rc::new(header.hidden),
         body: Some(vir_body),
     };
@@ -179,7 +239,7 @@ pub(crate) fn check_foreign_item_fn<'tcx>(
     generics: &Generics,
 ) -> Result<(), VirErr> {
     let ret = check_fn_decl(tcx, decl)?;
-    check_generics(generics)?;
+    let typ_params = check_generics(generics)?;
     let mode = get_mode(attrs);
     let fuel = get_fuel(attrs);
     let mut vir_params: Vec<vir::ast::Param> = Vec::new();
@@ -193,11 +253,14 @@ pub(crate) fn check_foreign_item_fn<'tcx>(
     let params = Rc::new(vir_params);
     let func = FunctionX {
         name,
+        typ_params,
         fuel,
         mode,
         params,
         ret,
         require: Rc::new(vec![]),
+        ensure: Rc::new(vec![]),
+        decreases: Rc::new(vec![]),
         hidden: Rc::new(vec![]),
         body: None,
     };
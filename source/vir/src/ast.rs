@@ -0,0 +1,197 @@
+//! The VIR (Verification IR) AST: what `rust_to_vir_*` lowers HIR/THIR down
+//! to, and what `Ctx`/`sst_to_air` consume to produce AIR queries. Every
+//! node is a `crate::def::Spanned<X>` so an error or diagnostic can always
+//! point back at the Rust source it came from.
+
+use crate::def::Spanned;
+use std::rc::Rc;
+
+pub type VirErr = Rc<Spanned<String>>;
+
+pub type Ident = Rc<String>;
+pub type Idents = Rc<Vec<Ident>>;
+
+/// A datatype's (or trait's, eventually) fully-qualified module path,
+/// represented as its component identifiers.
+pub type Path = Rc<Vec<Ident>>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Mode {
+    Spec,
+    Proof,
+    Exec,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TypX {
+    Bool,
+    Int,
+    Datatype(Path, Typs),
+    /// An opaque per-instantiation sort standing in for a function or
+    /// datatype's own type parameter -- see `def::typ_param_bind_vars`.
+    TypParam(Ident),
+}
+pub type Typ = Rc<TypX>;
+pub type Typs = Rc<Vec<Typ>>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Constant {
+    Bool(bool),
+    /// Arbitrary-precision (spec `int`/`nat`) and machine integer literals
+    /// alike are kept as their decimal text and only interpreted by AIR.
+    Int(Rc<String>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnaryOp {
+    Not,
+    Neg,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    And,
+    Or,
+    Eq,
+    Ne,
+    Le,
+    Ge,
+    Lt,
+    Gt,
+    Add,
+    Sub,
+    Mul,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quant {
+    Forall,
+    Exists,
+}
+
+/// A name paired with a payload -- used both for a constructor's field
+/// arguments (`name` = field name) and a quantifier's bound variables
+/// (`name` = variable name, payload = its `Typ`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Binder<A> {
+    pub name: Ident,
+    pub a: A,
+}
+pub type Binders<A> = Rc<Vec<Binder<A>>>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExprX {
+    Const(Constant),
+    Var(Ident),
+    Unary(UnaryOp, Expr),
+    Binary(BinaryOp, Expr, Expr),
+    If(Expr, Expr, Option<Expr>),
+    /// `match scrutinee { pat => arm, ... }`, kept as VIR arms rather than
+    /// desugared to `if`/accessor chains immediately -- `sst_to_air` does
+    /// that desugaring once, at axiom-emission time.
+    Match(Expr, Rc<Vec<Arm>>),
+    /// Build a datatype value: which datatype, which variant, and the
+    /// field bindings (tuple-style fields are named "0", "1", ... by
+    /// convention, the same as field-less positional construction).
+    Ctor(Path, Ident, Binders<Expr>),
+    /// Project one field out of a specific variant of a datatype value.
+    Field(Path, Ident, Ident, Expr),
+    /// `forall`/`exists` over one bound variable. When `body` is itself a
+    /// `Call("with_trigger", _, [groups, inner])`, the author's annotated
+    /// trigger groups win over the heuristic selector -- see
+    /// `sst_to_air::quant_to_air`, the only place that distinction matters.
+    Quant(Quant, Binders<Typ>, Expr),
+    /// A named call: to a user spec/proof/exec function, or to one of the
+    /// handful of builtins (`requires`/`ensures`/`decreases`/`assert`/
+    /// `assume`/`forall`/`exists`/`with_trigger`) that `rust_to_vir_expr`
+    /// recognizes by name instead of resolving to a VIR function. The
+    /// `Typs` are the call's type-parameter instantiation, used to pick
+    /// `box`/`unbox` coercions at monomorphization-free generic call sites.
+    Call(Ident, Typs, Exprs),
+    Block(Stmts, Option<Expr>),
+    /// A literal list of expressions -- currently only produced for the
+    /// `[[t1, t2], [t3]]`-shaped trigger-group argument of `with_trigger`.
+    Array(Exprs),
+    /// Marks `name` as hidden (fuel 0) for the rest of the enclosing block
+    /// -- the VIR form of a `hide(name)` header statement.
+    Fuel(Ident, u32),
+}
+pub type Expr = Rc<Spanned<ExprX>>;
+pub type Exprs = Rc<Vec<Expr>>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Arm {
+    pub path: Path,
+    pub variant: Ident,
+    /// One entry per field of `variant`: `name` is the field's own
+    /// declared name (`"0"`, `"1"`, ... for a tuple-style variant, the
+    /// field's own name for a struct-style one -- whatever
+    /// `variant_field_id` was declared under), `a` is the local name the
+    /// pattern bound it to (`_` patterns still get a fresh, unused name so
+    /// every field has an entry).
+    pub binders: Binders<Ident>,
+    pub body: Expr,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StmtX {
+    Expr(Expr),
+}
+pub type Stmt = Rc<Spanned<StmtX>>;
+pub type Stmts = Rc<Vec<Stmt>>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParamX {
+    pub name: Ident,
+    pub typ: Typ,
+}
+pub type Param = Rc<Spanned<ParamX>>;
+pub type Params = Rc<Vec<Param>>;
+
+#[derive(Clone, Debug)]
+pub struct FunctionX {
+    pub name: Ident,
+    pub typ_params: Idents,
+    pub mode: Mode,
+    /// Initial fuel for this function's own definition -- how many
+    /// `succ`s of unfolding a caller gets "for free" before needing to
+    /// spend its own. `0` corresponds to `#[verifier(opaque)]`/`hide`.
+    pub fuel: u32,
+    pub params: Params,
+    pub ret: Option<Typ>,
+    pub require: Exprs,
+    pub ensure: Exprs,
+    pub decreases: Exprs,
+    /// Other functions hidden (fuel forced to 0) for the scope of this
+    /// function's body.
+    pub hidden: Idents,
+    pub body: Option<Expr>,
+}
+pub type Function = Rc<Spanned<FunctionX>>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Field {
+    pub name: Ident,
+    pub typ: Typ,
+}
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Variant {
+    pub name: Ident,
+    pub fields: Rc<Vec<Field>>,
+}
+pub type Variants = Rc<Vec<Variant>>;
+
+#[derive(Clone, Debug)]
+pub struct DatatypeX {
+    pub path: Path,
+    pub typ_params: Idents,
+    pub variants: Variants,
+}
+pub type Datatype = Rc<Spanned<DatatypeX>>;
+
+#[derive(Clone, Debug, Default)]
+pub struct KrateX {
+    pub functions: Vec<Function>,
+    pub datatypes: Vec<Datatype>,
+}
+pub type Krate = Rc<KrateX>;
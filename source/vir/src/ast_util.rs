@@ -0,0 +1,82 @@
+//! Small helpers for building and reporting on VIR AST nodes that don't
+//! belong to any one pass: error construction and the textual rendering
+//! used for trigger diagnostics.
+
+use crate::ast::{BinaryOp, Expr, ExprX, UnaryOp, VirErr};
+use crate::def::Spanned;
+use air::ast::Span;
+
+/// Build a `VirErr` at `span` and wrap it in `Err`, so call sites can write
+/// `return err_string(&span, msg);` instead of `Err(Spanned::new(...))`.
+pub fn err_string<A>(span: &Span, msg: String) -> Result<A, VirErr> {
+    Err(Spanned::new(span.clone(), msg))
+}
+
+pub fn err_str<A>(span: &Span, msg: &str) -> Result<A, VirErr> {
+    err_string(span, msg.to_string())
+}
+
+/// Render an expression back to a short string, for use as a trigger term
+/// in diagnostics (e.g. "`--trigger` reported the term it matched on") and
+/// as the text `expr_to_trigger_strings` extracts from a manual
+/// `with_trigger` annotation.
+pub fn expr_to_string(expr: &Expr) -> String {
+    match &expr.x {
+        ExprX::Const(c) => format!("{:?}", c),
+        ExprX::Var(x) => x.to_string(),
+        ExprX::Unary(UnaryOp::Not, e) => format!("!{}", expr_to_string(e)),
+        ExprX::Unary(UnaryOp::Neg, e) => format!("-{}", expr_to_string(e)),
+        ExprX::Binary(op, e1, e2) => {
+            let s = match op {
+                BinaryOp::And => "&&",
+                BinaryOp::Or => "||",
+                BinaryOp::Eq => "==",
+                BinaryOp::Ne => "!=",
+                BinaryOp::Le => "<=",
+                BinaryOp::Ge => ">=",
+                BinaryOp::Lt => "<",
+                BinaryOp::Gt => ">",
+                BinaryOp::Add => "+",
+                BinaryOp::Sub => "-",
+                BinaryOp::Mul => "*",
+            };
+            format!("({} {} {})", expr_to_string(e1), s, expr_to_string(e2))
+        }
+        ExprX::Call(x, _, es) => {
+            let args: Vec<String> = es.iter().map(expr_to_string).collect();
+            format!("{}({})", x, args.join(", "))
+        }
+        ExprX::Field(_, _, field, e) => format!("{}.{}", expr_to_string(e), field),
+        ExprX::Ctor(_, variant, binders) => {
+            let args: Vec<String> =
+                binders.iter().map(|b| format!("{}: {}", b.name, expr_to_string(&b.a))).collect();
+            format!("{} {{ {} }}", variant, args.join(", "))
+        }
+        _ => "<expr>".to_string(),
+    }
+}
+
+/// Walk the `[[t1, t2], [t3]]`-shaped argument of a `with_trigger` call
+/// (lowered to `ExprX::Array` of `ExprX::Array`s, one per trigger group),
+/// applying `leaf` to each individual term. Shared by `expr_to_trigger_strings`
+/// (diagnostics, `leaf = expr_to_string`) and `sst_to_air::manual_triggers`
+/// (real SMT trigger terms, `leaf = expr_to_air`), so the two can't drift
+/// apart on what counts as a group vs. a bare single-term group.
+pub fn map_trigger_groups<T>(trigger_groups: &Expr, leaf: &mut impl FnMut(&Expr) -> T) -> Vec<Vec<T>> {
+    match &trigger_groups.x {
+        ExprX::Array(groups) => groups
+            .iter()
+            .map(|group| match &group.x {
+                ExprX::Array(terms) => terms.iter().map(|t| leaf(t)).collect(),
+                _ => vec![leaf(group)],
+            })
+            .collect(),
+        _ => vec![vec![leaf(trigger_groups)]],
+    }
+}
+
+/// Parse the `[[t1, t2], [t3]]`-shaped argument of a `with_trigger` call
+/// into the `Vec<Vec<String>>` shape `Ctx::record_chosen_triggers` wants.
+pub fn expr_to_trigger_strings(trigger_groups: &Expr) -> Vec<Vec<String>> {
+    map_trigger_groups(trigger_groups, &mut expr_to_string)
+}
@@ -0,0 +1,80 @@
+//! Generic recursive walk over a VIR `Expr` tree, rebuilding it bottom-up
+//! from whatever `f` returns for each node. Passes that only want to
+//! *observe* every subexpression (e.g. `Ctx::called_spec_functions`,
+//! `Ctx::scan_manual_triggers`) just return their argument unchanged.
+
+use crate::ast::{Arm, Expr, ExprX, StmtX, VirErr};
+use crate::def::Spanned;
+use std::rc::Rc;
+
+fn map_arm(arm: &Arm, f: &mut dyn FnMut(&Expr) -> Result<Expr, VirErr>) -> Result<Arm, VirErr> {
+    Ok(Arm {
+        path: arm.path.clone(),
+        variant: arm.variant.clone(),
+        binders: arm.binders.clone(),
+        body: map_expr_visitor(&arm.body, f)?,
+    })
+}
+
+/// Rebuild `expr`, recursively mapping every child first and then calling
+/// `f` on the (already-rebuilt) node itself.
+pub fn map_expr_visitor(
+    expr: &Expr,
+    f: &mut dyn FnMut(&Expr) -> Result<Expr, VirErr>,
+) -> Result<Expr, VirErr> {
+    let x = match &expr.x {
+        ExprX::Const(_) | ExprX::Var(_) | ExprX::Fuel(_, _) => expr.x.clone(),
+        ExprX::Unary(op, e1) => ExprX::Unary(*op, map_expr_visitor(e1, f)?),
+        ExprX::Binary(op, e1, e2) => {
+            ExprX::Binary(*op, map_expr_visitor(e1, f)?, map_expr_visitor(e2, f)?)
+        }
+        ExprX::If(cond, e1, e2) => {
+            let cond = map_expr_visitor(cond, f)?;
+            let e1 = map_expr_visitor(e1, f)?;
+            let e2 = e2.as_ref().map(|e| map_expr_visitor(e, f)).transpose()?;
+            ExprX::If(cond, e1, e2)
+        }
+        ExprX::Match(scrutinee, arms) => {
+            let scrutinee = map_expr_visitor(scrutinee, f)?;
+            let arms: Vec<Arm> = arms.iter().map(|a| map_arm(a, f)).collect::<Result<_, _>>()?;
+            ExprX::Match(scrutinee, Rc::new(arms))
+        }
+        ExprX::Ctor(path, variant, binders) => {
+            let binders = binders
+                .iter()
+                .map(|b| {
+                    Ok(crate::ast::Binder { name: b.name.clone(), a: map_expr_visitor(&b.a, f)? })
+                })
+                .collect::<Result<Vec<_>, VirErr>>()?;
+            ExprX::Ctor(path.clone(), variant.clone(), Rc::new(binders))
+        }
+        ExprX::Field(path, variant, field, e) => {
+            ExprX::Field(path.clone(), variant.clone(), field.clone(), map_expr_visitor(e, f)?)
+        }
+        ExprX::Quant(quant, binders, body) => {
+            ExprX::Quant(*quant, binders.clone(), map_expr_visitor(body, f)?)
+        }
+        ExprX::Call(name, typs, args) => {
+            let args: Vec<Expr> =
+                args.iter().map(|a| map_expr_visitor(a, f)).collect::<Result<_, _>>()?;
+            ExprX::Call(name.clone(), typs.clone(), Rc::new(args))
+        }
+        ExprX::Array(es) => {
+            let es: Vec<Expr> = es.iter().map(|e| map_expr_visitor(e, f)).collect::<Result<_, _>>()?;
+            ExprX::Array(Rc::new(es))
+        }
+        ExprX::Block(stmts, last) => {
+            let stmts: Vec<_> = stmts
+                .iter()
+                .map(|stmt| {
+                    let StmtX::Expr(e) = &stmt.x;
+                    Ok(Spanned::new(stmt.span.clone(), StmtX::Expr(map_expr_visitor(e, f)?)))
+                })
+                .collect::<Result<Vec<_>, VirErr>>()?;
+            let last = last.as_ref().map(|e| map_expr_visitor(e, f)).transpose()?;
+            ExprX::Block(Rc::new(stmts), last)
+        }
+    };
+    let rebuilt = Spanned::new(expr.span.clone(), x);
+    f(&rebuilt)
+}
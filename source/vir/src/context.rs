@@ -1,49 +1,157 @@
-use crate::ast::{Expr, ExprX, Function, Ident, Krate, Mode, Path, Variants, VirErr};
+use crate::ast::{Expr, ExprX, Function, Ident, Idents, Krate, Mode, Path, Variants, VirErr};
 use crate::ast_util::err_string;
 use crate::ast_visitor::map_expr_visitor;
-use crate::def::FUEL_ID;
-use air::ast::{Command, CommandX, Commands, DeclX, MultiOp, Span};
-use air::ast_util::str_typ;
+use crate::def::{prefix_fuel_id, FUEL_ID, FUEL_PARAM, SUCC};
+use air::ast::{BindX, Command, CommandX, Commands, DeclX, MultiOp, Quant, Span};
+use air::ast_util::{ident_apply, ident_var, str_typ};
 use std::collections::HashMap;
 use std::rc::Rc;
 
 pub struct Ctx {
     pub(crate) datatypes: HashMap<Path, Variants>,
+    // Each type parameter becomes an opaque SMT sort argument; values of that
+    // sort are boxed through the universal `Poly` type at use sites, so the
+    // datatype/function declarations below only need to quantify over the
+    // sort arguments themselves, not over any bound on them.
+    pub(crate) datatype_typ_params: HashMap<Path, Idents>,
     pub(crate) functions: Vec<Function>,
     pub(crate) func_map: HashMap<Ident, Function>,
-    pub(crate) chosen_triggers: std::cell::RefCell<Vec<(Span, Vec<Vec<String>>)>>, // diagnostics
+    // Functions are grouped by mutual-recursion SCC so that fuel-bounded
+    // definition axioms can be emitted per-component rather than per-function.
+    pub(crate) func_sccs: HashMap<Ident, usize>,
+    // diagnostics: one entry per quantifier, recording whether its triggers
+    // came from an author's `with_trigger`/`#[trigger]` annotation or from
+    // the heuristic selector, so output can tell the two apart.
+    pub(crate) chosen_triggers: std::cell::RefCell<Vec<(Span, Vec<Vec<String>>, TriggerSource)>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TriggerSource {
+    Inferred,
+    Manual,
 }
 
 impl Ctx {
-    fn check_defined_earlier(
-        func_map: &HashMap<Ident, Function>,
-        expr: &Expr,
-    ) -> Result<Expr, VirErr> {
-        match &expr.x {
-            ExprX::Call(x, _, _) | ExprX::Fuel(x, _) => {
-                if !func_map.contains_key(x) {
-                    return err_string(
-                        &expr.span,
-                        format!(
-                            "because support for recursion isn't yet implemented, {} must be defined before it is called",
-                            &x
-                        ),
-                    );
+    // Collect every spec-function name called (directly) from `expr`.
+    fn called_spec_functions(func_map: &HashMap<Ident, Function>, body: &Expr) -> Vec<Ident> {
+        let mut calls = Vec::new();
+        let _ = map_expr_visitor(body, &mut |expr| {
+            if let ExprX::Call(x, _, _) = &expr.x {
+                if func_map.contains_key(x) {
+                    calls.push(x.clone());
                 }
             }
-            _ => {}
+            Ok(expr.clone())
+        });
+        calls
+    }
+
+    // Tarjan's algorithm over the call graph induced by `func_map`, so that
+    // mutually recursive spec functions land in the same strongly connected
+    // component and can share a single fuel-bounded definition axiom group.
+    fn compute_sccs(func_map: &HashMap<Ident, Function>) -> HashMap<Ident, usize> {
+        struct State {
+            index: HashMap<Ident, usize>,
+            lowlink: HashMap<Ident, usize>,
+            on_stack: HashMap<Ident, bool>,
+            stack: Vec<Ident>,
+            next_index: usize,
+            next_scc: usize,
+            sccs: HashMap<Ident, usize>,
         }
-        Ok(expr.clone())
+        fn strongconnect(func_map: &HashMap<Ident, Function>, s: &mut State, v: &Ident) {
+            s.index.insert(v.clone(), s.next_index);
+            s.lowlink.insert(v.clone(), s.next_index);
+            s.next_index += 1;
+            s.stack.push(v.clone());
+            s.on_stack.insert(v.clone(), true);
+            let callees = match &func_map[v].x.body {
+                Some(body) => Ctx::called_spec_functions(func_map, body),
+                None => Vec::new(),
+            };
+            for w in callees {
+                if !s.index.contains_key(&w) {
+                    strongconnect(func_map, s, &w);
+                    let l = std::cmp::min(s.lowlink[v], s.lowlink[&w]);
+                    s.lowlink.insert(v.clone(), l);
+                } else if *s.on_stack.get(&w).unwrap_or(&false) {
+                    let l = std::cmp::min(s.lowlink[v], s.index[&w]);
+                    s.lowlink.insert(v.clone(), l);
+                }
+            }
+            if s.lowlink[v] == s.index[v] {
+                let scc = s.next_scc;
+                s.next_scc += 1;
+                loop {
+                    let w = s.stack.pop().unwrap();
+                    s.on_stack.insert(w.clone(), false);
+                    s.sccs.insert(w.clone(), scc);
+                    if &w == v {
+                        break;
+                    }
+                }
+            }
+        }
+        let mut s = State {
+            index: HashMap::new(),
+            lowlink: HashMap::new(),
+            on_stack: HashMap::new(),
+            stack: Vec::new(),
+            next_index: 0,
+            next_scc: 0,
+            sccs: HashMap::new(),
+        };
+        for name in func_map.keys() {
+            if !s.index.contains_key(name) {
+                strongconnect(func_map, &mut s, name);
+            }
+        }
+        s.sccs
     }
 
-    fn check_no_recursion(
+    // With recursion supported via fuel, the only remaining requirement is
+    // that a `decreases` measure (when present) strictly decreases on every
+    // recursive call, so the fuel-bounded unfolding is sound. When a function
+    // is self- or mutually-recursive and has no `decreases` clause, fall back
+    // to the old in-order check as a conservative default.
+    fn check_recursion(
         func_map: &HashMap<Ident, Function>,
+        sccs: &HashMap<Ident, usize>,
+        seen: &HashMap<Ident, Function>,
         function: &Function,
     ) -> Result<(), VirErr> {
-        // Recursion is not implemented yet, so make sure there is no recursion.
-        // Check this by simply forcing all the declarations to be in order.
-        if let Some(body) = &function.x.body {
-            map_expr_visitor(body, &mut |expr| Self::check_defined_earlier(func_map, expr))?;
+        let body = match &function.x.body {
+            Some(body) => body,
+            None => return Ok(()),
+        };
+        let my_scc = sccs[&function.x.name];
+        let calls = Self::called_spec_functions(func_map, body);
+        let recursive = calls.iter().any(|c| sccs[c] == my_scc);
+        if !recursive {
+            return Ok(());
+        }
+        // The decreases-based escape hatch relies on `termination_commands`
+        // emitting a real VC and `definition_axioms` emitting real
+        // fuel-bounded unfolding axioms -- both of which only exist for
+        // `Mode::Spec` bodies. A recursive `Mode::Exec`/`Mode::Proof`
+        // function has no such backing, so it still needs the syntactic
+        // in-order fallback below regardless of whether it has `decreases`.
+        if function.x.mode == Mode::Spec && function.x.decreases.len() > 0 {
+            // Soundness of the unfolding is established by the termination
+            // VC emitted in `termination_commands`; nothing further to check here.
+            return Ok(());
+        }
+        for callee in &calls {
+            if sccs[callee] == my_scc && !seen.contains_key(callee) {
+                return err_string(
+                    &body.span,
+                    format!(
+                        "{} is mutually recursive with {} but has no `decreases` clause, \
+                         so it must be defined after everything it calls",
+                        &function.x.name, callee
+                    ),
+                );
+            }
         }
         Ok(())
     }
@@ -54,16 +162,53 @@ impl Ctx {
             .iter()
             .map(|d| (d.x.path.clone(), d.x.variants.clone()))
             .collect::<HashMap<_, _>>();
+        let datatype_typ_params = krate
+            .datatypes
+            .iter()
+            .map(|d| (d.x.path.clone(), d.x.typ_params.clone()))
+            .collect::<HashMap<_, _>>();
+        let func_map: HashMap<Ident, Function> =
+            krate.functions.iter().map(|f| (f.x.name.clone(), f.clone())).collect();
+        let func_sccs = Self::compute_sccs(&func_map);
         let mut functions: Vec<Function> = Vec::new();
-        let mut func_map: HashMap<Ident, Function> = HashMap::new();
+        let mut seen: HashMap<Ident, Function> = HashMap::new();
         for function in krate.functions.iter() {
-            Self::check_no_recursion(&func_map, function)?;
+            Self::check_recursion(&func_map, &func_sccs, &seen, function)?;
             functions.push(function.clone());
-            func_map.insert(function.x.name.clone(), function.clone());
+            seen.insert(function.x.name.clone(), function.clone());
         }
-        let chosen_triggers: std::cell::RefCell<Vec<(Span, Vec<Vec<String>>)>> =
+        let chosen_triggers: std::cell::RefCell<Vec<(Span, Vec<Vec<String>>, TriggerSource)>> =
             std::cell::RefCell::new(Vec::new());
-        Ok(Ctx { datatypes, functions, func_map, chosen_triggers })
+        let ctx = Ctx { datatypes, datatype_typ_params, functions, func_map, func_sccs, chosen_triggers };
+        for function in ctx.functions.iter() {
+            if let Some(body) = &function.x.body {
+                ctx.scan_manual_triggers(body);
+            }
+        }
+        Ok(ctx)
+    }
+
+    // Walk `body` for `with_trigger([[t1, t2], [t3]], quantifier)` /
+    // `#[trigger]`-annotated quantifiers (lowered by `expr_to_vir` to the same
+    // `with_trigger` call form) and record each one against
+    // `TriggerSource::Manual`, overriding whatever the heuristic selector
+    // would have chosen for that quantifier.
+    fn scan_manual_triggers(&self, body: &Expr) {
+        let _ = map_expr_visitor(body, &mut |expr| {
+            if let ExprX::Call(x, _, es) = &expr.x {
+                if x.as_str() == crate::def::WITH_TRIGGER_IDENT {
+                    if let Some(trigger_groups) = es.get(0) {
+                        let triggers = crate::ast_util::expr_to_trigger_strings(trigger_groups);
+                        self.record_chosen_triggers(
+                            expr.span.clone(),
+                            triggers,
+                            TriggerSource::Manual,
+                        );
+                    }
+                }
+            }
+            Ok(expr.clone())
+        });
     }
 
     pub fn prelude(&self) -> Commands {
@@ -77,8 +222,8 @@ impl Ctx {
         for function in &self.functions {
             match (function.x.mode, function.x.body.as_ref()) {
                 (Mode::Spec, Some(_)) => {
-                    let id = crate::def::prefix_fuel_id(&function.x.name);
-                    ids.push(air::ast_util::ident_var(&id));
+                    let id = prefix_fuel_id(&function.x.name);
+                    ids.push(ident_var(&id));
                     let typ_fuel_id = str_typ(&FUEL_ID);
                     let decl = Rc::new(DeclX::Const(id, typ_fuel_id));
                     commands.push(Rc::new(CommandX::Global(decl)));
@@ -92,8 +237,194 @@ impl Ctx {
         Rc::new(commands)
     }
 
-    // Report chosen triggers as strings for printing diagnostics
-    pub fn get_chosen_triggers(&self) -> Vec<(Span, Vec<Vec<String>>)> {
+    // Fuel-bounded definition axioms: `forall args, fuel.
+    //   fuel_bool(succ(fuel)) ==> name(args) == body[name(..) := name(.., fuel)]`
+    // Recursive occurrences of `name` inside `body` are rewritten to consume
+    // one unit of the caller's fuel; non-recursive calls keep the default,
+    // unbounded fuel constant emitted by `fuel()`. The top-level call of a
+    // recursive spec function is seeded with `prefix_fuel_id`'s constant, so
+    // the solver only unfolds as deep as the caller's fuel allows.
+    pub fn definition_axioms(&self) -> Commands {
+        let mut commands: Vec<Command> = Vec::new();
+        for function in &self.functions {
+            let body = match (function.x.mode, &function.x.body) {
+                (Mode::Spec, Some(body)) => body,
+                _ => continue,
+            };
+            let my_scc = self.func_sccs[&function.x.name];
+            // Every function in the same SCC, not just `function` itself, can
+            // be mutually recursive with it; all of them need to be rewritten
+            // to consume the predecessor fuel `f`, or only literal self-calls
+            // would be fuel-bounded and siblings would keep unbounded fuel.
+            let scc_siblings: Vec<Ident> = self
+                .func_map
+                .keys()
+                .filter(|name| self.func_sccs[*name] == my_scc)
+                .cloned()
+                .collect();
+            let is_recursive = Self::called_spec_functions(&self.func_map, body)
+                .iter()
+                .any(|c| self.func_sccs[c] == my_scc);
+            let fuel_param = crate::def::suffix_local_id(&Rc::new(FUEL_PARAM.to_string()));
+            let fuel_expr = if is_recursive {
+                ident_apply(&Rc::new(SUCC.to_string()), &vec![ident_var(&fuel_param)])
+            } else {
+                ident_var(&prefix_fuel_id(&function.x.name))
+            };
+            let guard = ident_apply(&Rc::new(crate::def::FUEL_BOOL.to_string()), &vec![fuel_expr]);
+            // `crate::sst_to_air` lowers the already-elaborated function body
+            // to the `name(args) == body` equation, rewriting every call to a
+            // member of `scc_siblings` (not just literal self-calls) to pass
+            // the predecessor fuel `f`; calls outside the SCC keep their own
+            // default fuel constant.
+            let body_eq = crate::sst_to_air::func_def_axiom(
+                function,
+                is_recursive,
+                &fuel_param,
+                &scc_siblings,
+            );
+            let implies = air::ast_util::mk_implies(guard, body_eq);
+            // Generic functions quantify over their type parameters (each an
+            // opaque sort argument) in addition to the fuel and value
+            // parameters, so the one axiom covers every instantiation.
+            let mut bind_vars = crate::def::typ_param_bind_vars(&function.x.typ_params);
+            bind_vars.extend(
+                (*crate::sst_to_air::func_bind_vars(function, is_recursive, &fuel_param)).clone(),
+            );
+            let bind = Rc::new(BindX::Quant(Quant::Forall, Rc::new(bind_vars), Rc::new(vec![])));
+            let forall = Rc::new(air::ast::ExprX::Bind(bind, implies));
+            commands.push(Rc::new(CommandX::Global(Rc::new(DeclX::Axiom(forall)))));
+        }
+        Rc::new(commands)
+    }
+
+    // For every recursive (or mutually recursive) spec function with a
+    // `decreases` clause, emit a verification condition asserting that the
+    // measure strictly decreases (and stays non-negative) on each recursive
+    // call relative to the measure at the call site. This is what lets
+    // `check_recursion` skip the in-order fallback: termination is checked
+    // here, via SMT, instead of rejected syntactically.
+    pub fn termination_commands(&self) -> Commands {
+        let mut commands: Vec<Command> = Vec::new();
+        for function in &self.functions {
+            // Fuel-bounded unfolding (and thus this termination check) only
+            // applies to spec function definitions; `check_recursion` only
+            // grants the decreases-based escape hatch to `Mode::Spec` too,
+            // so the two must stay in lockstep.
+            if function.x.mode != Mode::Spec || function.x.decreases.len() == 0 {
+                continue;
+            }
+            for decreases_at_call in
+                crate::sst_to_air::recursive_call_measures(&self.func_map, function)
+            {
+                let assertion = crate::sst_to_air::decreases_check(
+                    &function.x.decreases,
+                    &decreases_at_call,
+                    function,
+                );
+                let query = Rc::new(air::ast::QueryX { local: Rc::new(vec![]), assertion });
+                commands.push(Rc::new(CommandX::CheckValid(query)));
+            }
+        }
+        Rc::new(commands)
+    }
+
+    // For every `Mode::Exec`/`Mode::Proof` function with an `ensures` clause,
+    // bind its return value to `result` and:
+    //  - emit a `CheckValid` VC at each of the function's return points,
+    //    substituting the returned expression for `result`, so the
+    //    postcondition is proved where the function is defined; and
+    //  - emit a global axiom `forall params, result. call(params, result) ==>
+    //    ensures(params, result)` so the same clauses are assumed (not
+    //    re-proved) at every call site.
+    pub fn postcondition_commands(&self) -> Commands {
+        let mut commands: Vec<Command> = Vec::new();
+        for function in &self.functions {
+            if function.x.mode == Mode::Spec || function.x.ensure.len() == 0 {
+                continue;
+            }
+            let result_id =
+                crate::def::suffix_local_id(&Rc::new(crate::def::RESULT_IDENT.to_string()));
+            for return_expr in crate::sst_to_air::return_points(function) {
+                let assertion =
+                    crate::sst_to_air::ensures_check(&function.x.ensure, &return_expr);
+                let query = Rc::new(air::ast::QueryX { local: Rc::new(vec![]), assertion });
+                commands.push(Rc::new(CommandX::CheckValid(query)));
+            }
+            let call_app = crate::sst_to_air::func_call_with_result(function, &result_id);
+            let ensures_expr = crate::sst_to_air::ensures_expr(&function.x.ensure);
+            let implies = air::ast_util::mk_implies(call_app, ensures_expr);
+            let bind_vars = crate::sst_to_air::func_bind_vars_with_result(function, &result_id);
+            let bind = Rc::new(BindX::Quant(Quant::Forall, bind_vars, Rc::new(vec![])));
+            let forall = Rc::new(air::ast::ExprX::Bind(bind, implies));
+            commands.push(Rc::new(CommandX::Global(Rc::new(DeclX::Axiom(forall)))));
+        }
+        Rc::new(commands)
+    }
+
+    // Called by `rust_to_vir_expr::expr_to_vir` once per `forall`/`exists`:
+    // either with the heuristically selected trigger terms, or -- when the
+    // author wrote `#[trigger]` / `with_trigger(...)` on the quantifier --
+    // with the annotated terms instead, overriding the heuristic entirely.
+    pub fn record_chosen_triggers(
+        &self,
+        span: Span,
+        triggers: Vec<Vec<String>>,
+        source: TriggerSource,
+    ) {
+        self.chosen_triggers.borrow_mut().push((span, triggers, source));
+    }
+
+    // Report chosen triggers as strings for printing diagnostics; callers
+    // that only care about the strings (not whether they were manual) can
+    // still destructure the first two elements of each tuple.
+    pub fn get_chosen_triggers(&self) -> Vec<(Span, Vec<Vec<String>>, TriggerSource)> {
         self.chosen_triggers.borrow().clone()
     }
+
+    // Structural axioms for a single datatype: an SMT `datatype` declaration
+    // (carrying a tester per variant and an accessor per field, so field
+    // projection and discriminant tests are handled natively by the solver)
+    // plus one constructor-injectivity axiom per variant:
+    //   forall f1..fn. accessor_i(Ctor(f1, .., fn)) == fi
+    // Distinct variants of the same datatype are unequal automatically,
+    // because the SMT datatype encoding gives each constructor its own
+    // tester; that, together with injectivity, makes two struct values equal
+    // iff all fields are equal, and two enum values equal iff they are the
+    // same variant with equal payloads.
+    // Declaring `path` via SMT-LIB's native datatype theory (`DeclX::Datatypes`
+    // below) already gives the solver, for free and without any quantifiers,
+    // that every accessor applied to its own constructor yields the field it
+    // was built from, and that distinct constructors (and thus distinct
+    // variants) produce distinct values. Earlier this function also emitted
+    // hand-rolled `forall` axioms for the former property; they were exact
+    // duplicates of what the datatype theory already enforces, and a pure
+    // liability -- every extra quantifier is a matching-loop/timeout risk for
+    // no additional soundness. Removed: trust the native theory instead.
+    fn datatype_commands(path: &Path, variants: &Variants, _typ_params: &Idents) -> Commands {
+        let datatype_id = crate::def::path_to_air_ident(path);
+        let mut air_variants: Vec<air::ast::Variant> = Vec::new();
+        for variant in variants.iter() {
+            let ctor_id = crate::def::variant_id(path, &variant.name);
+            let mut air_fields: Vec<air::ast::Field> = Vec::new();
+            for field in variant.fields.iter() {
+                let accessor_id = crate::def::variant_field_id(path, &variant.name, &field.name);
+                air_fields.push((accessor_id.clone(), crate::def::typ_to_air(&field.typ)));
+            }
+            air_variants.push((ctor_id, Rc::new(air_fields)));
+        }
+        let datatype_decl = Rc::new(DeclX::Datatypes(Rc::new(vec![(datatype_id, Rc::new(air_variants))])));
+        Rc::new(vec![Rc::new(CommandX::Global(datatype_decl))])
+    }
+
+    // Emit structural datatype axioms for every datatype in the krate,
+    // parallel to `fuel()` and `prelude()`.
+    pub fn datatype_axioms(&self) -> Commands {
+        let mut commands: Vec<Command> = Vec::new();
+        for (path, variants) in self.datatypes.iter() {
+            let typ_params = &self.datatype_typ_params[path];
+            commands.extend((*Self::datatype_commands(path, variants, typ_params)).clone());
+        }
+        Rc::new(commands)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,156 @@
+//! Names and small helpers shared between VIR itself and the AIR it lowers
+//! to: SMT sort/function identifiers, the span-wrapping node type every AST
+//! node is built from, and the few "mangle a VIR name into an AIR name"
+//! functions used throughout `context.rs` and `sst_to_air.rs`.
+
+use crate::ast::{Ident, Idents, Path, Typ, TypX};
+use air::ast::{Ident as AIdent, Typ as ATyp};
+use air::ast_util::{ident_apply, ident_var, str_typ};
+use std::rc::Rc;
+
+/// Every VIR AST node is a payload `X` tagged with the source span it came
+/// from, so error messages and diagnostics can always point somewhere.
+/// `VirErr = Rc<Spanned<String>>` reuses this for error messages themselves.
+#[derive(Clone, Debug)]
+pub struct Spanned<X> {
+    pub span: air::ast::Span,
+    pub x: X,
+}
+
+impl<X> Spanned<X> {
+    pub fn new(span: air::ast::Span, x: X) -> Rc<Spanned<X>> {
+        Rc::new(Spanned { span, x })
+    }
+}
+
+// SMT sort used to bound recursive-unfolding depth: an inductively-defined
+// `Fuel` with a `zero` base case and a `succ` successor, plus a `fuel_bool`
+// predicate that holds once at least one unit of fuel remains.
+pub const FUEL_ID: &str = "Fuel";
+pub const ZERO: &str = "zero";
+pub const SUCC: &str = "succ";
+pub const FUEL_BOOL: &str = "fuel_bool";
+
+// Name of the implicit extra parameter every recursive spec function takes,
+// so its own definition axiom can be stated in terms of "one less than the
+// caller's fuel" without colliding with any real Rust parameter name.
+pub const FUEL_PARAM: &str = "fuel%";
+
+// Reserved identifier bound, inside an `ensures` clause, to the exec/proof
+// function's return value. Not a legal Rust identifier suffix (the `%`),
+// so it can never collide with a real local variable.
+pub const RESULT_IDENT: &str = "result%";
+
+// The name `rust_to_vir_expr` lowers a genuine `with_trigger` builtin call
+// to (after confirming, via the callee's home crate, that it really is the
+// builtin and not a same-named user function) -- reserved the same way as
+// `FUEL_PARAM`/`RESULT_IDENT`, so `sst_to_air::manual_triggers`'s name
+// match can't be fooled downstream, where no crate information survives.
+pub const WITH_TRIGGER_IDENT: &str = "with_trigger%";
+
+// The universal boxed representation every generic (type-parameter-typed)
+// value is coerced through, and its sort name. `box`/`unbox` are declared
+// once per concrete sort that can actually be stored in a generic slot
+// (see `prelude::prelude_nodes`), since a single `Poly`-valued function
+// can't be generic over its own argument's sort -- `box_fn_name`/
+// `unbox_fn_name` below pick the right one for a given `Typ`.
+pub const POLY: &str = "Poly";
+const BOX_PREFIX: &str = "box";
+const UNBOX_PREFIX: &str = "unbox";
+
+fn ident(s: String) -> AIdent {
+    Rc::new(s)
+}
+
+/// Name of the `box` function that wraps a value of sort `typ` into `Poly`
+/// -- `box_Bool`, `box_Int`, `box_<Datatype>`, one per sort `prelude.rs`
+/// declares a pair for.
+pub fn box_fn_name(typ: &Typ) -> AIdent {
+    ident(format!("{}_{}", BOX_PREFIX, sort_name(typ)))
+}
+
+/// The `unbox` counterpart of `box_fn_name`.
+pub fn unbox_fn_name(typ: &Typ) -> AIdent {
+    ident(format!("{}_{}", UNBOX_PREFIX, sort_name(typ)))
+}
+
+fn sort_name(typ: &Typ) -> String {
+    match &**typ {
+        TypX::Bool => "Bool".to_string(),
+        TypX::Int => "Int".to_string(),
+        TypX::TypParam(_) => POLY.to_string(),
+        TypX::Datatype(path, _) => path_to_air_ident(path).to_string(),
+    }
+}
+
+/// The distinct, per-function fuel constant emitted by `Ctx::fuel` and
+/// referenced by non-recursive calls to `name` (recursive calls instead
+/// consume `FUEL_PARAM`, one `succ` at a time -- see `definition_axioms`).
+pub fn prefix_fuel_id(name: &Ident) -> AIdent {
+    ident(format!("fuel%{}", name))
+}
+
+/// Mangle a VIR-level local/bound variable name into the AIR identifier
+/// used for it wherever it's referenced as an SMT constant/bound variable.
+pub fn suffix_local_id(name: &Ident) -> AIdent {
+    ident(format!("{}@", name))
+}
+
+/// Flatten a datatype's module path into a single AIR sort identifier.
+pub fn path_to_air_ident(path: &Path) -> AIdent {
+    ident(path.iter().map(|s| s.as_str()).collect::<Vec<_>>().join("."))
+}
+
+/// AIR identifier for one variant's constructor function.
+pub fn variant_id(path: &Path, variant: &Ident) -> AIdent {
+    ident(format!("{}/{}", path_to_air_ident(path), variant))
+}
+
+/// AIR identifier for one field's accessor function within a variant.
+pub fn variant_field_id(path: &Path, variant: &Ident, field: &Ident) -> AIdent {
+    ident(format!("{}/{}/{}", path_to_air_ident(path), variant, field))
+}
+
+/// AIR identifier for one variant's discriminant tester (`is-Ctor`).
+pub fn variant_test_id(path: &Path, variant: &Ident) -> AIdent {
+    ident(format!("is-{}", variant_id(path, variant)))
+}
+
+/// Every value of a type parameter's sort is boxed through `Poly`, so a
+/// type parameter contributes exactly one opaque `Type` sort argument to
+/// whatever forall quantifies over it.
+pub fn typ_param_bind_vars(typ_params: &Idents) -> Vec<(AIdent, ATyp)> {
+    let typ_sort = str_typ(&ident("Type".to_string()));
+    typ_params.iter().map(|t| (suffix_local_id(t), typ_sort.clone())).collect()
+}
+
+/// The SMT sort a VIR `Typ` is represented by. Concretely-typed values keep
+/// their natural sort (`Bool`/`Int`), but a datatype's own fields (which
+/// may be instantiated at any type, including one of that datatype's own
+/// type parameters) always need a single closed sort to live in, so they're
+/// declared as `Poly` and boxed/unboxed at the few points that need to
+/// recover the concrete value.
+pub fn typ_to_air(typ: &Typ) -> ATyp {
+    match &**typ {
+        TypX::Bool => str_typ(&ident("Bool".to_string())),
+        TypX::Int => str_typ(&ident("Int".to_string())),
+        TypX::TypParam(_) => str_typ(&ident(POLY.to_string())),
+        TypX::Datatype(path, _) => str_typ(&path_to_air_ident(path)),
+    }
+}
+
+/// The `Type`-sorted term identifying `typ` at a generic call site -- the
+/// argument passed for each of the callee's type parameters (see
+/// `def::typ_param_bind_vars` for the corresponding binder on the
+/// definition side).
+pub fn typ_to_id(typ: &Typ) -> air::ast::Expr {
+    match &**typ {
+        TypX::Bool => ident_apply(&ident("TYPE%Bool".to_string()), &vec![]),
+        TypX::Int => ident_apply(&ident("TYPE%Int".to_string()), &vec![]),
+        TypX::TypParam(t) => ident_var(&suffix_local_id(t)),
+        TypX::Datatype(path, typs) => {
+            let args: Vec<air::ast::Expr> = typs.iter().map(typ_to_id).collect();
+            ident_apply(&ident(format!("TYPE%{}", path_to_air_ident(path))), &args)
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! The fixed set of SMT declarations every query needs regardless of the
+//! crate being verified: the `Fuel` sort that bounds recursive unfolding
+//! (see `context::definition_axioms`) and the `Poly`/`box`/`unbox` sort
+//! used to represent generic (type-parameter-typed) values uniformly.
+//!
+//! Written as `air::ast::Node` s-expressions (the same surface syntax
+//! `air::print_parse` accepts from a `.air` file) rather than built
+//! directly out of `air::ast::Decl`, since this is a fixed block of SMT
+//! declarations, not something derived from the crate being checked.
+
+use air::ast::Node;
+
+fn atom(s: &str) -> Node {
+    Node::Atom(s.to_string())
+}
+
+fn list(nodes: Vec<Node>) -> Node {
+    Node::List(nodes)
+}
+
+pub fn prelude_nodes() -> Vec<Node> {
+    vec![
+        // (declare-datatypes () ((Fuel zero (succ (prec Fuel)))))
+        list(vec![
+            atom("declare-datatypes"),
+            list(vec![]),
+            list(vec![list(vec![
+                atom("Fuel"),
+                atom("zero"),
+                list(vec![atom("succ"), list(vec![atom("prec"), atom("Fuel")])]),
+            ])]),
+        ]),
+        // (declare-fun fuel_bool (Fuel) Bool)
+        list(vec![
+            atom("declare-fun"),
+            atom("fuel_bool"),
+            list(vec![atom("Fuel")]),
+            atom("Bool"),
+        ]),
+        // fuel_bool holds as soon as one unit of fuel remains, and is
+        // downward closed: whatever held at `succ(f)` still holds at `f`.
+        list(vec![
+            atom("assert"),
+            list(vec![atom("forall"), list(vec![list(vec![atom("f"), atom("Fuel")])]), atom("(fuel_bool (succ f))")]),
+        ]),
+        // (declare-sort Type 0) -- one opaque sort per type parameter
+        // instantiation, boxed into/out of `Poly` at generic call sites.
+        list(vec![atom("declare-sort"), atom("Type"), atom("0")]),
+        list(vec![atom("declare-sort"), atom("Poly"), atom("0")]),
+    ]
+    .into_iter()
+    .chain(box_unbox_nodes("Bool"))
+    .chain(box_unbox_nodes("Int"))
+    .collect()
+}
+
+/// `box_<sort>`/`unbox_<sort>` and their round-trip axiom, for one concrete
+/// sort a generic (type-parameter-typed) slot can hold -- see
+/// `def::box_fn_name`/`unbox_fn_name`. Unlike `box`/`unbox` themselves,
+/// this pair genuinely can't be generic over `sort`, so one is declared
+/// per concrete sort actually used in a generic position; `Bool` and
+/// `Int` are the only ones this crate's examples ever instantiate a type
+/// parameter with.
+fn box_unbox_nodes(sort: &str) -> Vec<Node> {
+    let box_name = format!("box_{}", sort);
+    let unbox_name = format!("unbox_{}", sort);
+    vec![
+        list(vec![
+            atom("declare-fun"),
+            atom(&box_name),
+            list(vec![atom("Type"), atom(sort)]),
+            atom("Poly"),
+        ]),
+        list(vec![
+            atom("declare-fun"),
+            atom(&unbox_name),
+            list(vec![atom("Type"), atom("Poly")]),
+            atom(sort),
+        ]),
+        list(vec![
+            atom("assert"),
+            list(vec![
+                atom("forall"),
+                list(vec![list(vec![atom("t"), atom("Type")]), list(vec![atom("v"), atom(sort)])]),
+                atom(&format!("(= ({} t ({} t v)) v)", unbox_name, box_name)),
+            ]),
+        ]),
+    ]
+}
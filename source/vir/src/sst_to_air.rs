@@ -0,0 +1,439 @@
+//! Lowering from VIR expressions to the AIR expressions `Ctx` assembles
+//! into SMT queries. Named for the "SST" (simplified/statement-oriented)
+//! intermediate form the real pipeline passes through on the way to AIR;
+//! this crate has no separate SST pass yet, so it lowers straight from
+//! `vir::ast::Expr`.
+//!
+//! Everything here is pure: it reads a `Function`/`Expr` and returns an
+//! `air::ast::Expr`/`Commands` fragment for `context.rs` to assemble into
+//! full queries. None of it mutates its inputs.
+
+use crate::ast::{
+    Arm, BinaryOp, Constant, Expr, ExprX, Function, Ident, Idents, Params, Quant, UnaryOp,
+};
+use crate::ast_visitor::map_expr_visitor;
+use crate::def::{suffix_local_id, typ_to_air, typ_to_id};
+use air::ast_util::{ident_apply, ident_var, mk_and, mk_eq, mk_ite, mk_not, mk_or};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+fn name_expr(name: &str) -> air::ast::Expr {
+    ident_apply(&Rc::new(name.to_string()), &vec![])
+}
+
+fn int_const(n: &str) -> air::ast::Expr {
+    Rc::new(air::ast::ExprX::Const(air::ast::Constant::Nat(Rc::new(n.to_string()))))
+}
+
+/// Carries the information `expr_to_air` needs to rewrite a recursive
+/// spec function's own body for its definition axiom: every call to a
+/// member of the function's mutual-recursion SCC spends one unit of the
+/// caller's fuel (`fuel_expr`) instead of the callee's own default fuel
+/// constant -- see `context::definition_axioms`.
+struct FuelCtx<'a> {
+    scc_siblings: &'a [Ident],
+    fuel_expr: air::ast::Expr,
+}
+
+/// Lower a VIR expression to AIR, with no fuel-rewriting -- used for
+/// `requires`/`ensures`/`decreases` clauses and for bodies that aren't a
+/// recursive function's own definition.
+pub fn expr_to_air(expr: &Expr) -> air::ast::Expr {
+    expr_to_air_rec(expr, None)
+}
+
+fn expr_to_air_rec(expr: &Expr, fuel_ctx: Option<&FuelCtx>) -> air::ast::Expr {
+    match &expr.x {
+        ExprX::Const(Constant::Bool(b)) => {
+            Rc::new(air::ast::ExprX::Const(air::ast::Constant::Bool(*b)))
+        }
+        ExprX::Const(Constant::Int(s)) => int_const(s),
+        ExprX::Var(x) => ident_var(&suffix_local_id(x)),
+        ExprX::Unary(UnaryOp::Not, e) => mk_not(&expr_to_air_rec(e, fuel_ctx)),
+        ExprX::Unary(UnaryOp::Neg, e) => {
+            ident_apply(&Rc::new("-".to_string()), &vec![expr_to_air_rec(e, fuel_ctx)])
+        }
+        ExprX::Binary(op, e1, e2) => {
+            let a1 = expr_to_air_rec(e1, fuel_ctx);
+            let a2 = expr_to_air_rec(e2, fuel_ctx);
+            match op {
+                BinaryOp::And => mk_and(&vec![a1, a2]),
+                BinaryOp::Or => mk_or(&vec![a1, a2]),
+                BinaryOp::Eq => mk_eq(a1, a2),
+                BinaryOp::Ne => mk_not(&mk_eq(a1, a2)),
+                BinaryOp::Le => ident_apply(&Rc::new("<=".to_string()), &vec![a1, a2]),
+                BinaryOp::Ge => ident_apply(&Rc::new(">=".to_string()), &vec![a1, a2]),
+                BinaryOp::Lt => ident_apply(&Rc::new("<".to_string()), &vec![a1, a2]),
+                BinaryOp::Gt => ident_apply(&Rc::new(">".to_string()), &vec![a1, a2]),
+                BinaryOp::Add => ident_apply(&Rc::new("+".to_string()), &vec![a1, a2]),
+                BinaryOp::Sub => ident_apply(&Rc::new("-".to_string()), &vec![a1, a2]),
+                BinaryOp::Mul => ident_apply(&Rc::new("*".to_string()), &vec![a1, a2]),
+            }
+        }
+        ExprX::If(cond, e1, e2) => {
+            let c = expr_to_air_rec(cond, fuel_ctx);
+            let t = expr_to_air_rec(e1, fuel_ctx);
+            // A two-armed spec `if` always has an else branch (spec exprs
+            // are exhaustive); there is no statement-position `if` in VIR.
+            let e = expr_to_air_rec(e2.as_ref().expect("if-expression without else"), fuel_ctx);
+            mk_ite(&c, &t, &e)
+        }
+        ExprX::Match(scrutinee, arms) => match_to_air(scrutinee, arms, fuel_ctx),
+        ExprX::Ctor(path, variant, binders) => {
+            let args: Vec<air::ast::Expr> =
+                binders.iter().map(|b| expr_to_air_rec(&b.a, fuel_ctx)).collect();
+            ident_apply(&crate::def::variant_id(path, variant), &args)
+        }
+        ExprX::Field(path, variant, field, e) => {
+            let accessor = crate::def::variant_field_id(path, variant, field);
+            ident_apply(&accessor, &vec![expr_to_air_rec(e, fuel_ctx)])
+        }
+        ExprX::Quant(quant, binders, body) => quant_to_air(*quant, binders, body),
+        ExprX::Call(name, typs, args) => call_to_air(name, typs, args, fuel_ctx),
+        ExprX::Array(_) => {
+            // Only ever appears as the trigger-group argument of
+            // `with_trigger`, which `quant_to_air` consumes directly
+            // without lowering it to an ordinary value.
+            panic!("internal error: `with_trigger`'s trigger-group argument used as a value")
+        }
+        ExprX::Block(stmts, last) => {
+            for stmt in stmts.iter() {
+                let crate::ast::StmtX::Expr(e) = &stmt.x;
+                expr_to_air_rec(e, fuel_ctx);
+            }
+            match last {
+                Some(e) => expr_to_air_rec(e, fuel_ctx),
+                None => name_expr("true"),
+            }
+        }
+        ExprX::Fuel(_, _) => name_expr("true"),
+    }
+}
+
+fn call_to_air(
+    name: &Ident,
+    typs: &crate::ast::Typs,
+    args: &crate::ast::Exprs,
+    fuel_ctx: Option<&FuelCtx>,
+) -> air::ast::Expr {
+    let mut all: Vec<air::ast::Expr> = typs.iter().map(typ_to_id).collect();
+    all.extend(args.iter().map(|a| expr_to_air_rec(a, fuel_ctx)));
+    if let Some(fc) = fuel_ctx {
+        if fc.scc_siblings.iter().any(|s| s == name) {
+            all.push(fc.fuel_expr.clone());
+        }
+    }
+    ident_apply(&Rc::new(name.to_string()), &all)
+}
+
+// Desugar `match scrutinee { path::variant(binders) => body, ... }` into a
+// chain of `if is-variant(scrutinee) then body[binders := accessors] ...`,
+// since AIR (like raw SMT-LIB datatype theory) has no first-class match.
+fn match_to_air(scrutinee: &Expr, arms: &Rc<Vec<Arm>>, fuel_ctx: Option<&FuelCtx>) -> air::ast::Expr {
+    let scrutinee_air = expr_to_air_rec(scrutinee, fuel_ctx);
+    let mut result: Option<air::ast::Expr> = None;
+    for arm in arms.iter().rev() {
+        let test = ident_apply(
+            &crate::def::variant_test_id(&arm.path, &arm.variant),
+            &vec![scrutinee_air.clone()],
+        );
+        let mut body_air = expr_to_air_rec(&arm.body, fuel_ctx);
+        for binder in arm.binders.iter() {
+            let accessor = crate::def::variant_field_id(&arm.path, &arm.variant, &binder.name);
+            let value = ident_apply(&accessor, &vec![scrutinee_air.clone()]);
+            body_air = subst_air_ident(&body_air, &suffix_local_id(&binder.a), &value);
+        }
+        result = Some(match result {
+            None => body_air,
+            Some(rest) => mk_ite(&test, &body_air, &rest),
+        });
+    }
+    result.expect("match with no arms")
+}
+
+/// Substitute every occurrence of the AIR-level bound variable `from` with
+/// `to` inside `e`. Used only to plug a match arm's field accessors in for
+/// its pattern-bound names -- a narrow, local rewrite, not a general AIR
+/// expression walker (which would belong in `air`, not here).
+fn subst_air_ident(
+    e: &air::ast::Expr,
+    from: &air::ast::Ident,
+    to: &air::ast::Expr,
+) -> air::ast::Expr {
+    use air::ast::ExprX as AX;
+    match &**e {
+        AX::Var(x) if x == from => to.clone(),
+        AX::Apply(f, args) => {
+            let args: Vec<air::ast::Expr> =
+                args.iter().map(|a| subst_air_ident(a, from, to)).collect();
+            Rc::new(AX::Apply(f.clone(), Rc::new(args)))
+        }
+        _ => e.clone(),
+    }
+}
+
+// A simple (non-exhaustive, but sound) trigger heuristic: every function
+// call syntactically inside the quantifier body is its own trigger group.
+fn infer_triggers(body: &Expr) -> Vec<Vec<air::ast::Expr>> {
+    let mut triggers = Vec::new();
+    let _ = map_expr_visitor(body, &mut |e| {
+        if let ExprX::Call(name, _, _) = &e.x {
+            // A nested `with_trigger` (e.g. the body of an inner
+            // quantifier) isn't an ordinary call -- its first argument is
+            // the `[[t1, t2], [t3]]` trigger-group array, not a value
+            // `expr_to_air` can lower, and `quant_to_air`/`manual_triggers`
+            // already pulls the nested quantifier's own triggers out of it
+            // when that quantifier is lowered.
+            if name.as_str() != crate::def::WITH_TRIGGER_IDENT {
+                triggers.push(vec![expr_to_air(e)]);
+            }
+        }
+        Ok(e.clone())
+    });
+    triggers
+}
+
+/// Drill through a block with no real statements (just `|i: int| { expr }`'s
+/// trivial wrapping of its one expression, as opposed to a block that
+/// actually sequences statements) down to its last expression -- so a
+/// brace-bodied quantifier closure is recognized the same way as a bare-
+/// expression one.
+fn peel_trivial_block(body: &Expr) -> &Expr {
+    match &body.x {
+        ExprX::Block(stmts, Some(last)) if stmts.is_empty() => peel_trivial_block(last),
+        _ => body,
+    }
+}
+
+/// If `body` is itself `with_trigger(groups, inner)` (see
+/// `vir::ast::ExprX::Quant`'s doc comment), the author picked the trigger
+/// groups by hand: pull them out of the `[[t1, t2], [t3]]`-shaped `groups`
+/// array instead of leaving them to `infer_triggers`, and use `inner` (not
+/// the `with_trigger` call itself) as the quantifier's real body.
+fn manual_triggers(body: &Expr) -> Option<(Vec<Vec<air::ast::Expr>>, Expr)> {
+    match &peel_trivial_block(body).x {
+        ExprX::Call(name, _, args)
+            if name.as_str() == crate::def::WITH_TRIGGER_IDENT && args.len() == 2 =>
+        {
+            let triggers = crate::ast_util::map_trigger_groups(&args[0], &mut expr_to_air);
+            Some((triggers, args[1].clone()))
+        }
+        _ => None,
+    }
+}
+
+fn quant_to_air(quant: Quant, binders: &crate::ast::Binders<crate::ast::Typ>, body: &Expr) -> air::ast::Expr {
+    let (triggers, inner_body) = match manual_triggers(body) {
+        Some((triggers, inner)) => (triggers, inner),
+        None => (infer_triggers(body), body.clone()),
+    };
+    let bind_vars: Vec<(air::ast::Ident, air::ast::Typ)> =
+        binders.iter().map(|b| (suffix_local_id(&b.name), typ_to_air(&b.a))).collect();
+    let air_quant = match quant {
+        Quant::Forall => air::ast::Quant::Forall,
+        Quant::Exists => air::ast::Quant::Exists,
+    };
+    let bind = Rc::new(air::ast::BindX::Quant(air_quant, Rc::new(bind_vars), Rc::new(triggers)));
+    Rc::new(air::ast::ExprX::Bind(bind, expr_to_air(&inner_body)))
+}
+
+// --- Function application, definition axioms, and fuel-bounded recursion ---
+
+fn func_app(
+    typ_params: &Idents,
+    name: &Ident,
+    param_args: Vec<air::ast::Expr>,
+    fuel_arg: Option<air::ast::Expr>,
+) -> air::ast::Expr {
+    let mut all: Vec<air::ast::Expr> =
+        typ_params.iter().map(|t| ident_var(&suffix_local_id(t))).collect();
+    all.extend(param_args);
+    if let Some(f) = fuel_arg {
+        all.push(f);
+    }
+    ident_apply(&Rc::new(name.to_string()), &all)
+}
+
+fn params_as_air_args(params: &Params) -> Vec<air::ast::Expr> {
+    params.iter().map(|p| ident_var(&suffix_local_id(&p.x.name))).collect()
+}
+
+/// Bound variables for a function's definition/postcondition axiom: its
+/// own parameters, plus the fuel parameter when the axiom is stated in
+/// terms of "one unit of fuel" (a recursive function's own definition).
+pub fn func_bind_vars(
+    function: &Function,
+    is_recursive: bool,
+    fuel_param: &air::ast::Ident,
+) -> Rc<Vec<(air::ast::Ident, air::ast::Typ)>> {
+    let mut vars: Vec<(air::ast::Ident, air::ast::Typ)> = function
+        .x
+        .params
+        .iter()
+        .map(|p| (suffix_local_id(&p.x.name), typ_to_air(&p.x.typ)))
+        .collect();
+    if is_recursive {
+        let fuel_typ = air::ast_util::str_typ(&Rc::new(crate::def::FUEL_ID.to_string()));
+        vars.push((fuel_param.clone(), fuel_typ));
+    }
+    Rc::new(vars)
+}
+
+/// `name(typ_params, params[, fuel]) == body`, with every call from `body`
+/// to a member of `scc_siblings` rewritten to spend one unit of the
+/// caller's fuel (`succ(fuel_param)`, wrapped by `context::definition_axioms`
+/// behind the `fuel_bool` guard) instead of its own default fuel constant.
+pub fn func_def_axiom(
+    function: &Function,
+    is_recursive: bool,
+    fuel_param: &air::ast::Ident,
+    scc_siblings: &[Ident],
+) -> air::ast::Expr {
+    let body = function.x.body.as_ref().expect("func_def_axiom: function has no body");
+    let lhs = func_app(
+        &function.x.typ_params,
+        &function.x.name,
+        params_as_air_args(&function.x.params),
+        if is_recursive { Some(ident_var(fuel_param)) } else { None },
+    );
+    let rhs = if is_recursive {
+        let fuel_ctx = FuelCtx { scc_siblings, fuel_expr: ident_var(fuel_param) };
+        expr_to_air_rec(body, Some(&fuel_ctx))
+    } else {
+        expr_to_air(body)
+    };
+    mk_eq(lhs, rhs)
+}
+
+// --- Termination (`decreases`) checking ---
+
+/// One occurrence, inside a function's body, of a call to another (or the
+/// same) spec function -- the arguments it was called with, which the
+/// `decreases` measure gets evaluated at to check it went down.
+pub struct DecreasesAtCall {
+    pub args: crate::ast::Exprs,
+}
+
+fn substitute_params(e: &Expr, params: &Params, args: &crate::ast::Exprs) -> Expr {
+    let mut subst: HashMap<Ident, Expr> = HashMap::new();
+    for (p, a) in params.iter().zip(args.iter()) {
+        subst.insert(p.x.name.clone(), a.clone());
+    }
+    map_expr_visitor(e, &mut |e2| match &e2.x {
+        ExprX::Var(x) => Ok(subst.get(x).cloned().unwrap_or_else(|| e2.clone())),
+        _ => Ok(e2.clone()),
+    })
+    .expect("substitute_params: substitution callback is infallible")
+}
+
+/// Every call, anywhere in `function`'s body, to a function present in
+/// `func_map` -- candidate recursive/mutually-recursive call sites whose
+/// `decreases` measure needs checking.
+pub fn recursive_call_measures(
+    func_map: &HashMap<Ident, Function>,
+    function: &Function,
+) -> Vec<DecreasesAtCall> {
+    let mut calls = Vec::new();
+    if let Some(body) = &function.x.body {
+        let _ = map_expr_visitor(body, &mut |e| {
+            if let ExprX::Call(name, _, args) = &e.x {
+                if func_map.contains_key(name) {
+                    calls.push(DecreasesAtCall { args: args.clone() });
+                }
+            }
+            Ok(e.clone())
+        });
+    }
+    calls
+}
+
+/// `0 <= measure(args_at_call) < measure(params)`: the recursive call's
+/// measure is non-negative and strictly smaller than the measure at
+/// function entry, so fuel-bounded unfolding is sound (`check_recursion`
+/// defers to this VC instead of requiring in-order definition).
+pub fn decreases_check(
+    decreases: &crate::ast::Exprs,
+    call: &DecreasesAtCall,
+    function: &Function,
+) -> air::ast::Expr {
+    let measure_here = decreases.get(0).expect("decreases_check: empty decreases clause");
+    let measure_at_call = substitute_params(measure_here, &function.x.params, &call.args);
+    let lt = ident_apply(
+        &Rc::new("<".to_string()),
+        &vec![expr_to_air(&measure_at_call), expr_to_air(measure_here)],
+    );
+    let nonneg =
+        ident_apply(&Rc::new(">=".to_string()), &vec![expr_to_air(&measure_at_call), int_const("0")]);
+    mk_and(&vec![nonneg, lt])
+}
+
+// --- `ensures`/`result` postconditions ---
+
+fn substitute_result(e: &Expr, replacement: &Expr) -> Expr {
+    map_expr_visitor(e, &mut |e2| match &e2.x {
+        ExprX::Var(x) if x.as_str() == crate::def::RESULT_IDENT => Ok(replacement.clone()),
+        _ => Ok(e2.clone()),
+    })
+    .expect("substitute_result: substitution callback is infallible")
+}
+
+fn conjoin(exprs: &crate::ast::Exprs, f: impl Fn(&Expr) -> air::ast::Expr) -> air::ast::Expr {
+    let air_exprs: Vec<air::ast::Expr> = exprs.iter().map(f).collect();
+    if air_exprs.is_empty() {
+        name_expr("true")
+    } else {
+        mk_and(&air_exprs)
+    }
+}
+
+/// The function's return-point expressions: just its tail expression,
+/// since VIR exec/proof bodies have no early `return` yet.
+pub fn return_points(function: &Function) -> Vec<Expr> {
+    let body = function.x.body.as_ref().expect("return_points: function has no body");
+    match &body.x {
+        ExprX::Block(_, Some(last)) => vec![last.clone()],
+        ExprX::Block(_, None) => vec![],
+        _ => vec![body.clone()],
+    }
+}
+
+/// All `ensures` clauses, with `result` substituted for `return_expr`,
+/// conjoined into the VC proved at one of the function's own return points.
+pub fn ensures_check(ensure: &crate::ast::Exprs, return_expr: &Expr) -> air::ast::Expr {
+    conjoin(ensure, |e| expr_to_air(&substitute_result(e, return_expr)))
+}
+
+/// `name(typ_params, params) == result`, for use as the antecedent of the
+/// call-site postcondition axiom (`context::postcondition_commands`).
+pub fn func_call_with_result(function: &Function, result_id: &air::ast::Ident) -> air::ast::Expr {
+    let call =
+        func_app(&function.x.typ_params, &function.x.name, params_as_air_args(&function.x.params), None);
+    mk_eq(call, ident_var(result_id))
+}
+
+/// All `ensures` clauses, conjoined into the consequent of the call-site
+/// axiom. `result` lowers (via `Var(RESULT_IDENT)`, see `rust_to_vir_expr`)
+/// straight to the same AIR identifier `func_bind_vars_with_result` binds
+/// for it, so no substitution is needed here -- unlike `ensures_check`,
+/// which proves the postcondition against a real return-point expression
+/// and so does need `substitute_result`.
+pub fn ensures_expr(ensure: &crate::ast::Exprs) -> air::ast::Expr {
+    conjoin(ensure, expr_to_air)
+}
+
+/// Bound variables for the call-site postcondition axiom: the function's
+/// own parameters plus `result`.
+pub fn func_bind_vars_with_result(
+    function: &Function,
+    result_id: &air::ast::Ident,
+) -> Rc<Vec<(air::ast::Ident, air::ast::Typ)>> {
+    let mut vars: Vec<(air::ast::Ident, air::ast::Typ)> = function
+        .x
+        .params
+        .iter()
+        .map(|p| (suffix_local_id(&p.x.name), typ_to_air(&p.x.typ)))
+        .collect();
+    let ret_typ = function.x.ret.as_ref().expect("func_bind_vars_with_result: function has no return type");
+    vars.push((result_id.clone(), typ_to_air(ret_typ)));
+    Rc::new(vars)
+}